@@ -10,17 +10,20 @@ statements=(
     "cargo clippy --no-default-features --features secret,mock -- -D warnings"
     "cargo clippy --no-default-features --features secret,mock,derive -- -D warnings"
     "cargo clippy --no-default-features --features full,mock -- -D warnings"
+    "cargo clippy --no-default-features --features full,mock,watch,async,clap,regex -- -D warnings"
 
     "cargo test --no-default-features --features derive"
     "cargo test --no-default-features --features derive,persist"
     "cargo test --no-default-features --features derive,persist,default_config_dir"
     "cargo test --no-default-features --features derive,persist,secret,mock"
     "cargo test --no-default-features --features derive,persist,secret,mock,default_config_dir"
+    "cargo test --no-default-features --features derive,persist,watch,async"
+    "cargo test --no-default-features --features derive,persist,clap,regex"
 
     "cargo run --example example --no-default-features --features full,mock"
     "cargo run --example example --no-default-features --features full,mock,default_config_dir"
 
-    "cargo doc --no-deps --no-default-features --features full,mock"
+    "cargo doc --no-deps --no-default-features --features full,mock,watch,async,clap,regex"
 )
 
 # loop echo and executing statements
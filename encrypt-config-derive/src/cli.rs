@@ -0,0 +1,57 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Lit, Meta, Type};
+
+pub(crate) fn derive_cli_source(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = input.data else {
+        panic!("`CliSource` can only be derived for structs.");
+    };
+    let Fields::Named(fields) = data.fields else {
+        panic!("`CliSource` can only be derived for structs with named fields.");
+    };
+
+    let args = fields.named.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap().to_string();
+        let id = ident.trim_start_matches("r#");
+        let long = id.replace('_', "-");
+        let help = field
+            .attrs
+            .iter()
+            .filter_map(|attr| match &attr.meta {
+                Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                    Expr::Lit(lit) => match &lit.lit {
+                        Lit::Str(s) => Some(s.value().trim().to_owned()),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let help = (!help.is_empty()).then(|| quote! { .help(#help) });
+        let is_bool = matches!(&field.ty, Type::Path(ty) if ty.path.is_ident("bool"));
+        let flag = is_bool.then(|| quote! { .num_args(0..=1).default_missing_value("true") });
+        quote! {
+            ::encrypt_config::cli::clap::Arg::new(#id)
+                .long(#long)
+                .value_name("VALUE")
+                #flag
+                #help
+        }
+    });
+
+    let expanded = quote! {
+        impl #impl_generics ::encrypt_config::cli::CliSource for #name #ty_generics #where_clause {
+            fn args() -> Vec<::encrypt_config::cli::clap::Arg> {
+                vec![#(#args),*]
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
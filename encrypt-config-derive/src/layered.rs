@@ -0,0 +1,72 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Expr};
+
+pub(crate) fn derive_layered_source(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut layers: Option<Expr> = None;
+
+    if let Some(attr) = input
+        .attrs
+        .iter()
+        .find(|&attr| attr.path().is_ident("source"))
+    {
+        attr.parse_nested_meta(|meta| {
+            match &meta.path {
+                path if path.is_ident("layers") => {
+                    let value = meta.value()?; // this parses the `=`
+                    layers = value.parse().ok();
+                }
+                _ => Err(meta.error("unsupported attribute"))?,
+            }
+            Ok(())
+        })
+        .unwrap();
+    };
+
+    if layers.is_none() {
+        panic!("`#[source(layers = ...)]` is required.");
+    }
+
+    let expanded = quote! {
+        impl #impl_generics ::encrypt_config::source::LayeredSource for #name #ty_generics #where_clause {
+            fn layers() -> Vec<::encrypt_config::layered::Layer> {
+                #layers
+            }
+        }
+
+        impl #impl_generics ::encrypt_config::source::Cacheable for #name #ty_generics #where_clause {
+            fn load() -> ::std::io::Result<Self>
+            where
+                Self: Sized,
+            {
+                <Self as ::encrypt_config::LayeredSource>::load()
+            }
+
+            fn store(&self) -> ::std::io::Result<()> {
+                <Self as ::encrypt_config::LayeredSource>::store(self)
+            }
+
+            fn source_path() -> Option<::std::path::PathBuf> {
+                <Self as ::encrypt_config::LayeredSource>::path()
+            }
+
+            fn source_kind() -> ::encrypt_config::source::SourceKind {
+                ::encrypt_config::source::SourceKind::Layered
+            }
+
+            fn as_any(&self) -> &dyn ::std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::std::any::Any {
+                self
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
@@ -6,15 +6,25 @@
 #[cfg(all(not(feature = "persist"), feature = "default_config_dir"))]
 compile_error!("Feature `default_config_dir` only works with feature `persist` on.");
 
+#[cfg(feature = "clap")]
+mod cli;
+#[cfg(feature = "persist")]
+mod layered;
 mod normal;
 #[cfg(feature = "persist")]
+mod overlay;
+#[cfg(feature = "persist")]
 mod persist;
 #[cfg(feature = "secret")]
 mod secret;
+mod validate;
 
 use proc_macro::TokenStream;
 
 /// Derive macro for `NormalSource`.
+///
+/// With `#[source(env_prefix = "APP_")]`, the environment variables like `APP_DATABASE__PORT` override the
+/// default value, which needs the type to be `Serialize` and `Deserialize` and feature `persist` on.
 /// # Example
 /// ```no_run
 /// # use encrypt_config_derive::NormalSource;
@@ -23,12 +33,14 @@ use proc_macro::TokenStream;
 ///     count: i32,
 /// }
 /// ```
-#[proc_macro_derive(NormalSource)]
+#[proc_macro_derive(NormalSource, attributes(source))]
 pub fn derive_normal_source(input: TokenStream) -> TokenStream {
     normal::derive_normal_source(input)
 }
 
 /// Derive macro for `PersistSource`.
+///
+/// The attributes in `#[source(...)]` are listed in the docs of `encrypt_config::PersistSource`.
 /// # Example
 /// ```no_run
 /// # use encrypt_config_derive::PersistSource;
@@ -53,7 +65,34 @@ pub fn derive_persist_source(input: TokenStream) -> TokenStream {
     persist::derive_persist_source(input)
 }
 
+/// Derive macro for `LayeredSource`, the expression of `layers` is evaluated on each load and store.
+/// # Example
+/// ```no_run
+/// # use encrypt_config_derive::LayeredSource;
+/// # use serde::{Deserialize, Serialize};
+/// # use encrypt_config::Layer;
+/// #[derive(Serialize, Deserialize, Default, LayeredSource)]
+/// #[source(layers = vec![
+///     Layer::value(&LayeredConfig::default()),
+///     Layer::file("/etc/app/config.json"),
+///     Layer::writable_file("/home/user/.config/app/config.json"),
+///     Layer::env("APP_"),
+///     Layer::args(std::env::args().skip(1)),
+/// ])]
+/// struct LayeredConfig {
+///    name: String,
+///    verbose: bool,
+/// }
+/// ```
+#[cfg(feature = "persist")]
+#[proc_macro_derive(LayeredSource, attributes(source))]
+pub fn derive_layered_source(input: TokenStream) -> TokenStream {
+    layered::derive_layered_source(input)
+}
+
 /// Derive macro for `SecretSource`.
+///
+/// The attributes in `#[source(...)]` are listed in the docs of `encrypt_config::SecretSource`.
 /// # Example
 /// ```no_run
 /// # use encrypt_config_derive::SecretSource;
@@ -76,3 +115,59 @@ pub fn derive_persist_source(input: TokenStream) -> TokenStream {
 pub fn derive_secret_source(input: TokenStream) -> TokenStream {
     secret::derive_secret_source(input)
 }
+
+/// Derive macro for `CliSource`, generating a long flag for each field, e.g. `--log-level <VALUE>` for `log_level`.
+/// The doc comments of the fields are taken as the help.
+///
+/// The `PersistSource` and `SecretSource` derived with `#[source(cli)]` apply the overrides recorded,
+/// which needs the type to be `Default`. See `encrypt_config::cli` for details.
+/// # Example
+/// ```no_run
+/// # use encrypt_config_derive::{CliSource, PersistSource};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize, Default, PersistSource, CliSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    doc = "#[source(name = \"app_config.json\", cli)]"
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    doc = "#[source(path = \"/path/to/app_config.json\", cli)]"
+)]
+/// struct AppConfig {
+///    /// The log level.
+///    log_level: String,
+///    verbose: bool,
+/// }
+/// ```
+#[cfg(feature = "clap")]
+#[proc_macro_derive(CliSource)]
+pub fn derive_cli_source(input: TokenStream) -> TokenStream {
+    cli::derive_cli_source(input)
+}
+
+/// Derive macro for `Validate`, generating the checks of the field rules in `#[config(...)]`:
+/// - `range(min = 1, max = 65535)`: either `min` or `max` can be omitted.
+/// - `regex = "..."`: the string must match the pattern, which needs feature `regex` on.
+///   The pattern is a string literal, checked at compile time.
+/// - `non_empty`: the string or collection must not be empty.
+/// - `one_of("a", "b")`: must equal one of the values.
+/// - `path_exists`: the path must exist.
+/// - `nested`: validate the field whose type implements `Validate`, the errors are prefixed by the field name.
+///
+/// The errors name the offending field path, e.g. `database.port`.
+/// # Example
+/// ```no_run
+/// # use encrypt_config_derive::Validate;
+/// #[derive(Validate)]
+/// struct ServerConfig {
+///     #[config(range(min = 1, max = 65535))]
+///     port: u16,
+///     #[config(non_empty, one_of("debug", "info"))]
+///     log_level: String,
+/// }
+/// ```
+#[proc_macro_derive(Validate, attributes(config))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    validate::derive_validate(input)
+}
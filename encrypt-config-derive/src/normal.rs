@@ -1,12 +1,37 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, DeriveInput, Expr};
 
 pub(crate) fn derive_normal_source(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
+    let mut env_prefix: Option<Expr> = None;
+
+    if let Some(attr) = input
+        .attrs
+        .iter()
+        .find(|&attr| attr.path().is_ident("source"))
+    {
+        attr.parse_nested_meta(|meta| {
+            match &meta.path {
+                path if path.is_ident("env_prefix") => {
+                    let value = meta.value()?; // this parses the `=`
+                    env_prefix = value.parse().ok();
+                }
+                _ => Err(meta.error("unsupported attribute"))?,
+            }
+            Ok(())
+        })
+        .unwrap();
+    };
+
+    let load = match env_prefix {
+        None => quote! { Ok(Self::default()) },
+        Some(env_prefix) => quote! { ::encrypt_config::env::overlay(Self::default(), #env_prefix) },
+    };
+
     let expanded = quote! {
         impl #impl_generics ::encrypt_config::source::NormalSource for #name #ty_generics #where_clause { }
 
@@ -15,13 +40,17 @@ pub(crate) fn derive_normal_source(input: TokenStream) -> TokenStream {
             where
                 Self: Sized,
             {
-                Ok(Self::default())
+                #load
             }
 
             fn store(&self) -> ::std::io::Result<()> {
                 Ok(())
             }
 
+            fn source_kind() -> ::encrypt_config::source::SourceKind {
+                ::encrypt_config::source::SourceKind::Normal
+            }
+
             fn as_any(&self) -> &dyn ::std::any::Any {
                 self
             }
@@ -0,0 +1,115 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Expr;
+
+/// `load`, `refresh` and `store` of `Cacheable` forwarding to `source_trait`, with the overlays and validation applied if any.
+///
+/// The overlays are applied on the loaded value, which falls back to default before overlaying as `Cacheable::fallback()` allows.
+/// And the overlaid fields are restored to the file's on storing, so they are never written back,
+/// or to the defaults if the file is not found, whatever `Cacheable::fallback()` is, so that the file can be created.
+///
+/// The value is validated after overlaid on loading, unless it fell back to default, and before written on storing.
+pub(crate) fn load_store(
+    source_trait: TokenStream,
+    env_prefix: Option<Expr>,
+    cli: bool,
+    validate: Option<Expr>,
+) -> TokenStream {
+    let mut overlays = vec![];
+    // Restoring from `self` for the first one, and from the value restored by the previous one for the rest.
+    let mut restores = vec![];
+    let mut restored = quote! { self };
+    if let Some(env_prefix) = env_prefix {
+        overlays.push(quote! { ::encrypt_config::env::overlay(value, #env_prefix)? });
+        restores.push(
+            quote! { ::encrypt_config::env::without_overlay(#restored, &base, #env_prefix)? },
+        );
+        restored = quote! { &value };
+    }
+    if cli {
+        overlays.push(quote! { ::encrypt_config::cli::overlay(value)? });
+        restores.push(quote! { ::encrypt_config::cli::without_overlay(#restored, &base)? });
+        restored = quote! { &value };
+    }
+
+    let validate_loaded = validate.as_ref().map(|validate| {
+        quote! { ::encrypt_config::validate::ensure(&value, #validate)?; }
+    });
+    let validate_restored = validate.as_ref().map(|validate| {
+        quote! { ::encrypt_config::validate::ensure(#restored, #validate)?; }
+    });
+
+    if overlays.is_empty() {
+        let load = |method: TokenStream| {
+            quote! {
+                fn #method() -> ::std::io::Result<Self>
+                where
+                    Self: Sized,
+                {
+                    let value = <Self as #source_trait>::#method()?;
+                    #validate_loaded
+                    Ok(value)
+                }
+            }
+        };
+        let (load, refresh) = (load(quote! { load }), load(quote! { refresh }));
+        return quote! {
+            #load
+
+            #refresh
+
+            fn store(&self) -> ::std::io::Result<()> {
+                #validate_restored
+                <Self as #source_trait>::store(self)
+            }
+        };
+    }
+
+    let base = |method: &TokenStream| {
+        quote! {
+            match <Self as #source_trait>::#method() {
+                Err(e) if <Self as ::encrypt_config::source::Cacheable>::fallback().allows(&e) => {
+                    (Self::default(), true)
+                }
+                res => (res?, false),
+            }
+        }
+    };
+    let load = |method: TokenStream| {
+        let loaded = base(&method);
+        quote! {
+            fn #method() -> ::std::io::Result<Self>
+            where
+                Self: Sized,
+            {
+                let (value, fell_back) = #loaded;
+                #(let value = #overlays;)*
+                if !fell_back {
+                    #validate_loaded
+                }
+                Ok(value)
+            }
+        }
+    };
+    let (load, refresh) = (load(quote! { load }), load(quote! { refresh }));
+    // Reading instead of loading before writing back, so that the file is not recorded as loaded.
+    // The file not found is the default whatever the fallback, so that it can be created.
+    let read = quote! {
+        match <Self as #source_trait>::read() {
+            Err(e) if e.kind() == ::std::io::ErrorKind::NotFound => Self::default(),
+            res => res?,
+        }
+    };
+    quote! {
+        #load
+
+        #refresh
+
+        fn store(&self) -> ::std::io::Result<()> {
+            let base = #read;
+            #(let value = #restores;)*
+            #validate_restored
+            <Self as #source_trait>::store(#restored)
+        }
+    }
+}
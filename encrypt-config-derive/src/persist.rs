@@ -1,6 +1,7 @@
+use crate::{overlay::load_store, validate::has_field_rules};
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Expr};
+use syn::{parse_macro_input, parse_quote, DeriveInput, Expr};
 
 pub(crate) fn derive_persist_source(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -8,6 +9,15 @@ pub(crate) fn derive_persist_source(input: TokenStream) -> TokenStream {
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
     let mut path_or_name: Option<Expr> = None;
+    let mut validate: Option<Expr> = None;
+    let mut fallback: Option<Expr> = None;
+    let mut backups: Option<Expr> = None;
+    let mut lock: Option<Expr> = None;
+    let mut conflict: Option<Expr> = None;
+    let mut env_prefix: Option<Expr> = None;
+    let mut version: Option<Expr> = None;
+    let mut migrations: Option<Expr> = None;
+    let mut cli = false;
 
     if let Some(attr) = input
         .attrs
@@ -26,6 +36,44 @@ pub(crate) fn derive_persist_source(input: TokenStream) -> TokenStream {
                     let value = meta.value()?; // this parses the `=`
                     path_or_name = value.parse().ok();
                 }
+                path if path.is_ident("env_prefix") => {
+                    let value = meta.value()?; // this parses the `=`
+                    env_prefix = value.parse().ok();
+                }
+                path if path.is_ident("validate") => {
+                    let value = meta.value()?; // this parses the `=`
+                    validate = value.parse().ok();
+                }
+                path if path.is_ident("fallback") => {
+                    let value = meta.value()?; // this parses the `=`
+                    fallback = value.parse().ok();
+                }
+                path if path.is_ident("backups") => {
+                    let value = meta.value()?; // this parses the `=`
+                    backups = value.parse().ok();
+                }
+                path if path.is_ident("lock") => {
+                    let value = meta.value()?; // this parses the `=`
+                    lock = value.parse().ok();
+                }
+                path if path.is_ident("conflict") => {
+                    let value = meta.value()?; // this parses the `=`
+                    conflict = value.parse().ok();
+                }
+                path if path.is_ident("version") => {
+                    let value = meta.value()?; // this parses the `=`
+                    version = value.parse().ok();
+                }
+                path if path.is_ident("migrations") => {
+                    let value = meta.value()?; // this parses the `=`
+                    migrations = value.parse().ok();
+                }
+                path if path.is_ident("cli") => {
+                    if cfg!(not(feature = "clap")) {
+                        Err(meta.error("`cli` requires the `clap` feature of `encrypt_config`"))?
+                    }
+                    cli = true;
+                }
                 _ => Err(meta.error("unsupported attribute"))?,
             }
             Ok(())
@@ -33,6 +81,10 @@ pub(crate) fn derive_persist_source(input: TokenStream) -> TokenStream {
         .unwrap();
     };
 
+    if validate.is_none() && has_field_rules(&input.data) {
+        validate = Some(parse_quote! { <Self as ::encrypt_config::Validate>::validate });
+    }
+
     if path_or_name.is_none() {
         #[cfg(feature = "default_config_dir")]
         panic!("`#[source(name = \"...\")]` is required.");
@@ -40,10 +92,25 @@ pub(crate) fn derive_persist_source(input: TokenStream) -> TokenStream {
         panic!("`#[source(path = \"...\")]` is required.");
     }
 
+    let backups = backups.map(|backups| quote! { const BACKUPS: usize = #backups; });
+    let lock = lock.map(|lock| quote! { const LOCK: ::encrypt_config::lock::LockMode = #lock; });
+    let conflict = conflict.map(|conflict| {
+        quote! { const CONFLICT: ::encrypt_config::conflict::ConflictPolicy = #conflict; }
+    });
+    let version = version.map(|version| quote! { const VERSION: u32 = #version; });
+    let migrations = migrations.map(|migrations| {
+        quote! { const MIGRATIONS: &'static [::encrypt_config::migrate::Migration] = &#migrations; }
+    });
+
     #[cfg(not(feature = "default_config_dir"))]
     let persist_source_impl = quote! {
         impl #impl_generics ::encrypt_config::source::PersistSource for #name #ty_generics #where_clause {
             const PATH: &'static str = #path_or_name;
+            #version
+            #migrations
+            #backups
+            #lock
+            #conflict
         }
     };
 
@@ -51,22 +118,42 @@ pub(crate) fn derive_persist_source(input: TokenStream) -> TokenStream {
     let persist_source_impl = quote! {
         impl #impl_generics ::encrypt_config::source::PersistSource for #name #ty_generics #where_clause {
             const NAME: &'static str = #path_or_name;
+            #version
+            #migrations
+            #backups
+            #lock
+            #conflict
         }
     };
 
+    let load_store = load_store(
+        quote! { ::encrypt_config::PersistSource },
+        env_prefix,
+        cli,
+        validate,
+    );
+
+    let fallback = fallback.map(|fallback| {
+        quote! {
+            fn fallback() -> ::encrypt_config::source::Fallback {
+                #fallback
+            }
+        }
+    });
+
     let expanded = quote! {
         #persist_source_impl
 
         impl #impl_generics ::encrypt_config::source::Cacheable for #name #ty_generics #where_clause {
-            fn load() -> ::std::io::Result<Self>
-            where
-                Self: Sized,
-            {
-                <Self as ::encrypt_config::PersistSource>::load()
+            #load_store
+            #fallback
+
+            fn source_path() -> Option<::std::path::PathBuf> {
+                Some(<Self as ::encrypt_config::PersistSource>::path())
             }
 
-            fn store(&self) -> ::std::io::Result<()> {
-                <Self as ::encrypt_config::PersistSource>::store(self)
+            fn source_kind() -> ::encrypt_config::source::SourceKind {
+                ::encrypt_config::source::SourceKind::Persist
             }
 
             fn as_any(&self) -> &dyn ::std::any::Any {
@@ -1,6 +1,7 @@
+use crate::{overlay::load_store, validate::has_field_rules};
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Expr};
+use syn::{parse_macro_input, parse_quote, DeriveInput, Expr};
 
 pub(crate) fn derive_secret_source(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -8,7 +9,13 @@ pub(crate) fn derive_secret_source(input: TokenStream) -> TokenStream {
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
     let mut path_or_name: Option<Expr> = None;
+    let mut validate: Option<Expr> = None;
+    let mut fallback: Option<Expr> = None;
+    let mut backups: Option<Expr> = None;
+    let mut lock: Option<Expr> = None;
+    let mut conflict: Option<Expr> = None;
     let mut keyring_entry: Option<Expr> = None;
+    let mut cli = false;
 
     if let Some(attr) = input
         .attrs
@@ -31,6 +38,32 @@ pub(crate) fn derive_secret_source(input: TokenStream) -> TokenStream {
                     let value = meta.value()?; // this parses the `=`
                     keyring_entry = value.parse().ok();
                 }
+                path if path.is_ident("validate") => {
+                    let value = meta.value()?; // this parses the `=`
+                    validate = value.parse().ok();
+                }
+                path if path.is_ident("fallback") => {
+                    let value = meta.value()?; // this parses the `=`
+                    fallback = value.parse().ok();
+                }
+                path if path.is_ident("backups") => {
+                    let value = meta.value()?; // this parses the `=`
+                    backups = value.parse().ok();
+                }
+                path if path.is_ident("lock") => {
+                    let value = meta.value()?; // this parses the `=`
+                    lock = value.parse().ok();
+                }
+                path if path.is_ident("conflict") => {
+                    let value = meta.value()?; // this parses the `=`
+                    conflict = value.parse().ok();
+                }
+                path if path.is_ident("cli") => {
+                    if cfg!(not(feature = "clap")) {
+                        Err(meta.error("`cli` requires the `clap` feature of `encrypt_config`"))?
+                    }
+                    cli = true;
+                }
                 _ => Err(meta.error("unsupported attribute"))?,
             }
             Ok(())
@@ -38,6 +71,10 @@ pub(crate) fn derive_secret_source(input: TokenStream) -> TokenStream {
         .unwrap();
     };
 
+    if validate.is_none() && has_field_rules(&input.data) {
+        validate = Some(parse_quote! { <Self as ::encrypt_config::Validate>::validate });
+    }
+
     if path_or_name.is_none() {
         #[cfg(feature = "default_config_dir")]
         panic!("`#[source(name = \"...\")]` is required.");
@@ -49,11 +86,20 @@ pub(crate) fn derive_secret_source(input: TokenStream) -> TokenStream {
         panic!("`#[source(keyring_entry = \"...\")]` is required.");
     }
 
+    let backups = backups.map(|backups| quote! { const BACKUPS: usize = #backups; });
+    let lock = lock.map(|lock| quote! { const LOCK: ::encrypt_config::lock::LockMode = #lock; });
+    let conflict = conflict.map(|conflict| {
+        quote! { const CONFLICT: ::encrypt_config::conflict::ConflictPolicy = #conflict; }
+    });
+
     #[cfg(not(feature = "default_config_dir"))]
     let secret_source_impl = quote! {
         impl #impl_generics ::encrypt_config::source::SecretSource for #name #ty_generics #where_clause {
             const PATH: &'static str = #path_or_name;
             const KEYRING_ENTRY: &'static str = #keyring_entry;
+            #backups
+            #lock
+            #conflict
         }
     };
 
@@ -62,22 +108,44 @@ pub(crate) fn derive_secret_source(input: TokenStream) -> TokenStream {
         impl #impl_generics ::encrypt_config::source::SecretSource for #name #ty_generics #where_clause {
             const NAME: &'static str = #path_or_name;
             const KEYRING_ENTRY: &'static str = #keyring_entry;
+            #backups
+            #lock
+            #conflict
         }
     };
 
+    let load_store = load_store(
+        quote! { ::encrypt_config::SecretSource },
+        None,
+        cli,
+        validate,
+    );
+
+    let fallback = fallback.map(|fallback| {
+        quote! {
+            fn fallback() -> ::encrypt_config::source::Fallback {
+                #fallback
+            }
+        }
+    });
+
     let expanded = quote! {
         #secret_source_impl
 
         impl #impl_generics ::encrypt_config::source::Cacheable for #name #ty_generics #where_clause {
-            fn load() -> ::std::io::Result<Self>
-            where
-                Self: Sized,
-            {
-                <Self as ::encrypt_config::SecretSource>::load()
+            #load_store
+            #fallback
+
+            fn source_path() -> Option<::std::path::PathBuf> {
+                Some(<Self as ::encrypt_config::SecretSource>::path())
+            }
+
+            fn source_kind() -> ::encrypt_config::source::SourceKind {
+                ::encrypt_config::source::SourceKind::Secret
             }
 
-            fn store(&self) -> ::std::io::Result<()> {
-                <Self as ::encrypt_config::SecretSource>::store(self)
+            fn keyring_entry() -> Option<&'static str> {
+                Some(<Self as ::encrypt_config::SecretSource>::KEYRING_ENTRY)
             }
 
             fn as_any(&self) -> &dyn ::std::any::Any {
@@ -0,0 +1,156 @@
+use proc_macro::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{
+    parenthesized, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Data, DeriveInput,
+    Expr, Fields, LitStr, Token,
+};
+
+pub(crate) fn derive_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = input.data else {
+        panic!("`Validate` can only be derived for structs.");
+    };
+    let Fields::Named(fields) = data.fields else {
+        panic!("`Validate` can only be derived for structs with named fields.");
+    };
+
+    let mut checks = vec![];
+    for field in fields.named.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let path = ident.to_string().trim_start_matches("r#").to_owned();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("config")) {
+            let parsed = attr.parse_nested_meta(|meta| {
+                let check = match &meta.path {
+                    p if p.is_ident("range") => {
+                        let mut min: Option<Expr> = None;
+                        let mut max: Option<Expr> = None;
+                        meta.parse_nested_meta(|meta| {
+                            match &meta.path {
+                                p if p.is_ident("min") => min = Some(meta.value()?.parse()?),
+                                p if p.is_ident("max") => max = Some(meta.value()?.parse()?),
+                                _ => Err(meta.error("unsupported attribute"))?,
+                            }
+                            Ok(())
+                        })?;
+                        let (cond, message) = match (min, max) {
+                            (Some(min), Some(max)) => (
+                                quote! { (#min..=#max).contains(&self.#ident) },
+                                quote! { format!("must be in {}..={}", #min, #max) },
+                            ),
+                            (Some(min), None) => (
+                                quote! { self.#ident >= #min },
+                                quote! { format!("must be at least {}", #min) },
+                            ),
+                            (None, Some(max)) => (
+                                quote! { self.#ident <= #max },
+                                quote! { format!("must be at most {}", #max) },
+                            ),
+                            (None, None) => Err(meta.error("`min` or `max` is required"))?,
+                        };
+                        quote! {
+                            if !(#cond) {
+                                errors.push(::encrypt_config::FieldError::new(#path, #message));
+                            }
+                        }
+                    }
+                    p if p.is_ident("regex") && cfg!(not(feature = "regex")) => {
+                        meta.value()?.parse::<LitStr>()?;
+                        quote_spanned! { p.span() =>
+                            ::core::compile_error!("`regex` requires the `regex` feature of `encrypt_config`");
+                        }
+                    }
+                    p if p.is_ident("regex") => {
+                        let pattern: LitStr = meta.value()?.parse()?;
+                        #[cfg(feature = "regex")]
+                        regex::Regex::new(&pattern.value()).map_err(|e| {
+                            syn::Error::new_spanned(&pattern, format!("invalid regex: {e}"))
+                        })?;
+                        quote! {
+                            if !::encrypt_config::validate::is_match(#pattern, &self.#ident) {
+                                errors.push(::encrypt_config::FieldError::new(
+                                    #path,
+                                    format!("must match `{}`", #pattern),
+                                ));
+                            }
+                        }
+                    }
+                    p if p.is_ident("non_empty") => quote! {
+                        if self.#ident.is_empty() {
+                            errors.push(::encrypt_config::FieldError::new(#path, "must not be empty"));
+                        }
+                    },
+                    p if p.is_ident("one_of") => {
+                        let content;
+                        parenthesized!(content in meta.input);
+                        let values = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+                        let values = values.iter().collect::<Vec<_>>();
+                        quote! {
+                            if ![#(#values),*].iter().any(|v| self.#ident == *v) {
+                                errors.push(::encrypt_config::FieldError::new(
+                                    #path,
+                                    format!("must be one of {:?}", [#(#values),*]),
+                                ));
+                            }
+                        }
+                    }
+                    p if p.is_ident("path_exists") => quote! {
+                        let file: &::std::path::Path = self.#ident.as_ref();
+                        if !file.exists() {
+                            errors.push(::encrypt_config::FieldError::new(
+                                #path,
+                                format!("path `{}` does not exist", file.display()),
+                            ));
+                        }
+                    },
+                    p if p.is_ident("nested") => quote! {
+                        if let Err(nested) = ::encrypt_config::Validate::validate(&self.#ident) {
+                            errors.extend(nested.into_iter().map(|e| {
+                                ::encrypt_config::FieldError::new(
+                                    format!("{}.{}", #path, e.field),
+                                    e.message,
+                                )
+                            }));
+                        }
+                    },
+                    _ => Err(meta.error("unsupported attribute"))?,
+                };
+                checks.push(check);
+                Ok(())
+            });
+            // Reported at the attribute, e.g. an invalid regex.
+            if let Err(e) = parsed {
+                return e.to_compile_error().into();
+            }
+        }
+    }
+
+    let expanded = quote! {
+        impl #impl_generics ::encrypt_config::Validate for #name #ty_generics #where_clause {
+            fn validate(&self) -> Result<(), Vec<::encrypt_config::FieldError>> {
+                let mut errors = vec![];
+                #({ #checks })*
+                match errors.is_empty() {
+                    true => Ok(()),
+                    false => Err(errors),
+                }
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Whether any field has `#[config(...)]`, so that `Validate` is expected to be derived.
+#[cfg(feature = "persist")]
+pub(crate) fn has_field_rules(data: &Data) -> bool {
+    match data {
+        Data::Struct(data) => data
+            .fields
+            .iter()
+            .any(|f| f.attrs.iter().any(|a| a.path().is_ident("config"))),
+        _ => false,
+    }
+}
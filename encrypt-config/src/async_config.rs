@@ -0,0 +1,130 @@
+//! # Async Config
+//! This module provides an [`AsyncConfig`], whose loading and writing back run off the async executor threads.
+
+use crate::{
+    config::{CfgMut, CfgRef, Config},
+    error::{ConfigResult, TaskCancelled},
+    source::Cacheable,
+};
+use snafu::ResultExt;
+use std::{marker::PhantomData, sync::Arc};
+
+/// An async wrapper of [`Config`].
+///
+/// [`Cacheable::load()`] and [`Cacheable::store()`] of the sources do blocking io, and the secret sources
+/// even do blocking keyring and rsa work, which would stall the async runtime.
+/// So `AsyncConfig` runs them on the blocking threads of `tokio` through [`tokio::task::spawn_blocking`].
+///
+/// Caution: dirty values are still written back when the last `AsyncConfig` dropped, which is blocking.
+/// Call [`AsyncConfig::flush_all()`] before dropping it in async context.
+///
+/// This must be used within a `tokio` runtime.
+pub struct AsyncConfig<const N: usize> {
+    inner: Arc<Config<N>>,
+}
+
+impl<const N: usize> Default for AsyncConfig<N> {
+    /// Create an empty [`AsyncConfig`] cache.
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Config::default()),
+        }
+    }
+}
+
+impl<const N: usize> Clone for AsyncConfig<N> {
+    /// Clone the handle, the cache is shared.
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<const N: usize> AsyncConfig<N> {
+    /// Create a new [`AsyncConfig`] cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The inner [`Config`], whose methods are blocking on cache miss or writing back.
+    pub fn blocking(&self) -> &Config<N> {
+        &self.inner
+    }
+
+    /// Get an immutable ref ([`CfgRef`]) from the config.
+    /// If the value was not cached, it's loaded from the source on a blocking thread first,
+    /// and loaded again if evicted or invalidated meanwhile, it's never loaded on the executor thread.
+    ///
+    /// See [`Config::try_get()`] for the errors.
+    pub async fn get<T>(&self) -> ConfigResult<CfgRef<'_, T>>
+    where
+        T: Cacheable + Default,
+    {
+        loop {
+            self.preload::<T>().await?;
+            if let Some(line) = self.inner.cache.get_cached::<T>()? {
+                return Ok(CfgRef {
+                    line,
+                    _phantom: PhantomData,
+                });
+            }
+        }
+    }
+
+    /// Get a mutable ref ([`CfgMut`]) from the config.
+    /// If the value was not cached, it's loaded from the source on a blocking thread first,
+    /// and loaded again if evicted or invalidated meanwhile, it's never loaded on the executor thread.
+    ///
+    /// See [`Config::try_get_mut()`] for the errors.
+    pub async fn get_mut<T>(&self) -> ConfigResult<CfgMut<'_, T>>
+    where
+        T: Cacheable + Default,
+    {
+        loop {
+            self.preload::<T>().await?;
+            if let Some(line) = self.inner.cache.get_mut_cached::<T>()? {
+                return Ok(CfgMut {
+                    line,
+                    _phantom: PhantomData,
+                });
+            }
+        }
+    }
+
+    /// Write back the value of `T` on a blocking thread if it is cached and marked dirty.
+    ///
+    /// See [`Config::flush()`] for the errors.
+    pub async fn flush<T>(&self) -> ConfigResult<()>
+    where
+        T: Cacheable,
+    {
+        self.spawn_blocking(|cfg| cfg.flush::<T>()).await
+    }
+
+    /// Write back all the cached values marked dirty on a blocking thread.
+    ///
+    /// See [`Config::flush_all()`] for the errors.
+    pub async fn flush_all(&self) -> ConfigResult<()> {
+        self.spawn_blocking(|cfg| cfg.flush_all()).await
+    }
+
+    async fn preload<T>(&self) -> ConfigResult<()>
+    where
+        T: Cacheable + Default,
+    {
+        self.spawn_blocking(|cfg| cfg.cache.preload::<T>()).await
+    }
+
+    async fn spawn_blocking<F>(&self, f: F) -> ConfigResult<()>
+    where
+        F: FnOnce(&Config<N>) -> ConfigResult<()> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        match tokio::task::spawn_blocking(move || f(&inner)).await {
+            Ok(res) => res,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(e).context(TaskCancelled),
+        }
+    }
+}
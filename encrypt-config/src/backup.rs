@@ -0,0 +1,92 @@
+//! # Backup
+//! Backups of the files behind [`PersistSource`](crate::source::PersistSource) and [`SecretSource`](crate::source::SecretSource).
+//!
+//! With `#[source(backups = 3)]` on the derive macros, the file is copied to `<file>.bak.1` before each write-back,
+//! and the older backups are shifted to `<file>.bak.2` and `<file>.bak.3`, the oldest one beyond is dropped.
+//!
+//! When the file fails to parse on loading, it's moved to `<file>.corrupt-<timestamp>` (unix seconds with nanoseconds),
+//! and the newest valid backup is restored. If no backup is valid, the error is returned.
+//! For the secret files, a wrong key fails the same as a corrupt file, so the file is only moved if a backup can be decrypted.
+//!
+//! The file is never recovered when refreshed on `Config::watch()` (feature `watch`), since it may be half-written
+//! by the editor, the cached value is kept instead.
+
+use crate::file::{with_suffix, write_atomic};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The path of the `n`th backup of `path`, e.g. `config.json.bak.1`, the 1st one is the newest.
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, format!(".bak.{n}"))
+}
+
+/// Copy `path` to its 1st backup, shifting the older ones and keeping at most `retention` backups.
+/// Nothing is done if `retention` is `0` or `path` does not exist.
+pub(crate) fn rotate(path: &Path, retention: usize) -> io::Result<()> {
+    if retention == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..retention).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            std::fs::rename(from, backup_path(path, n + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Read and parse `path`. If it's corrupt and `retention` is not `0`, recover it from the newest backup which `parse` accepts,
+/// the corrupt file is moved aside. If no backup is accepted, the corrupt file is moved aside only if `quarantine_anyway`.
+pub(crate) fn read_or_recover<T>(
+    path: &Path,
+    retention: usize,
+    quarantine_anyway: bool,
+    parse: impl Fn(&[u8]) -> io::Result<T>,
+) -> io::Result<T> {
+    let e = match parse(&std::fs::read(path)?) {
+        Err(e) if retention > 0 && is_corrupt(&e) => e,
+        res => return res,
+    };
+    for n in 1..=retention {
+        let backup = backup_path(path, n);
+        let Ok(bytes) = std::fs::read(&backup) else {
+            continue;
+        };
+        if let Ok(value) = parse(&bytes) {
+            quarantine(path)?;
+            write_atomic(path, &bytes)?;
+            return Ok(value);
+        }
+    }
+    if quarantine_anyway {
+        quarantine(path)?;
+    }
+    Err(e)
+}
+
+/// Move `path` to `<path>.corrupt-<timestamp>`, with a counter appended if taken.
+fn quarantine(path: &Path) -> io::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let timestamp = format!("{}.{:09}", now.as_secs(), now.subsec_nanos());
+    let mut to = with_suffix(path, format!(".corrupt-{timestamp}"));
+    let mut n = 1;
+    while to.exists() {
+        n += 1;
+        to = with_suffix(path, format!(".corrupt-{timestamp}-{n}"));
+    }
+    std::fs::rename(path, to)
+}
+
+/// The errors of parsing, the others (e.g. permission denied or a newer version) are not caused by a corrupt file.
+fn is_corrupt(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+    )
+}
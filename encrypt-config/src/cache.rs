@@ -0,0 +1,882 @@
+//! # Cache
+//! The cache storage behind [`Config`](crate::Config).
+//!
+//! This is derived from [rom_cache](https://crates.io/crates/rom_cache), with write-back exposed
+//! so that [`Config`](crate::Config) can flush dirty values on demand.
+
+use crate::{
+    error::{
+        from_source_error, AlreadyBorrowed, CacheBusy, ConfigError, ConfigResult, FlushFailed,
+        LoadFailed, StoreFailed,
+    },
+    inspect::{BorrowState, CfgEntry},
+    source::{Cacheable, SourceKind},
+    stats::Counters,
+    subscribe::{CfgEvicted, Subscribers},
+};
+use snafu::IntoError;
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::UnsafeCell,
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+/// A cache storage holding at most N [`Cacheable`]s, unbounded if N is 0.
+///
+/// load a Cacheable into memory:
+/// 1. cache hit: update LRU
+/// 2. cache not full: load Cacheable into a new cache line
+/// 3. cache full: evict the least recently used cache line which is not being used
+///
+/// Dirty cache lines are written back when:
+/// 1. The `Cache` is dropped.
+/// 2. The `CacheLine` holding the dirty `Cacheable` is evicted.
+/// 3. The `CacheLine` is flushed.
+pub(crate) struct Cache<const N: usize> {
+    inner: Arc<CacheInner>,
+}
+
+/// The cache lines, shared with the watchers.
+pub(crate) struct CacheInner {
+    /// Cache lines ordered by LRU, the most recently used one comes first.
+    lines: Mutex<Vec<Arc<CacheLine>>>,
+    /// Subscribers of each type, kept even if the cache line is evicted.
+    subscribers: Mutex<HashMap<TypeId, Arc<Subscribers>>>,
+    /// Counters of each type, kept even if the cache line is evicted.
+    counters: Mutex<HashMap<TypeId, Arc<Counters>>>,
+    /// Subscribers of the evictions.
+    evictions: Arc<Subscribers>,
+    /// Wakes up the threads waiting for a ref to be released.
+    released: Arc<Released>,
+}
+
+impl<const N: usize> Default for Cache<N> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(CacheInner {
+                lines: Mutex::new(Vec::with_capacity(N)),
+                subscribers: Default::default(),
+                counters: Default::default(),
+                evictions: Default::default(),
+                released: Default::default(),
+            }),
+        }
+    }
+}
+
+impl Drop for CacheInner {
+    fn drop(&mut self) {
+        let lines = self.lines.get_mut().unwrap_or_else(|e| e.into_inner());
+        for line in lines.iter() {
+            if line.flag.is_dirty() {
+                // Safety: `&mut self` guarantees no ref is alive.
+                line.counters.store(|| unsafe { line.value() }.store()).ok();
+            }
+        }
+    }
+}
+
+impl CacheInner {
+    fn lock(&self) -> Lines<'_> {
+        // The lines are always consistent, since values are never touched during modifying the Vec.
+        Lines {
+            lines: Some(self.lines.lock().unwrap_or_else(|e| e.into_inner())),
+            evicted: vec![],
+            evictions: &self.evictions,
+        }
+    }
+
+    /// Subscribers of `T`, created if not exists.
+    pub(crate) fn subscribers<T: Cacheable>(&self) -> Arc<Subscribers> {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.entry(TypeId::of::<T>()).or_default().clone()
+    }
+
+    /// Counters of `T`, created if not exists.
+    fn counters<T: Cacheable>(&self) -> Arc<Counters> {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Arc::new(Counters::new(type_name::<T>())))
+            .clone()
+    }
+
+    /// Refresh the cache line of `T` from the source, since the source was changed.
+    /// See `CacheLine::refresh()`.
+    #[cfg(feature = "watch")]
+    pub(crate) fn refresh<T: Cacheable>(&self) {
+        let lines = self.lock();
+        let Some(line) = lines
+            .iter()
+            .find(|l| l.type_id == TypeId::of::<T>())
+            .cloned()
+        else {
+            return;
+        };
+        line.refresh::<T>();
+        let notify = line.read_to_notify_changed();
+        drop(lines);
+        if notify {
+            line.notify_reading();
+        }
+    }
+}
+
+impl<const N: usize> Cache<N> {
+    /// Retrieve a cache line for reading.
+    /// - ConfigError::AlreadyBorrowed: cache hit, but the cache line is being written.
+    /// - ConfigError::CacheBusy: cache miss, and all cache lines are being used.
+    /// - ConfigError::StoreFailed: cache miss, and the dirty cache line evicted cannot be written back.
+    /// - ConfigError::LoadFailed: cache miss, and the value cannot be loaded.
+    pub(crate) fn get<T: Cacheable + Default>(&self) -> ConfigResult<Arc<CacheLine>> {
+        let mut lines = self.lock();
+        let line = self.load::<T>(&mut lines)?;
+        Self::begin_read::<T>(lines, line)
+    }
+
+    /// Retrieve a cache line for writing.
+    /// - ConfigError::AlreadyBorrowed: cache hit, but the cache line is being read or written.
+    /// - ConfigError::CacheBusy: cache miss, and all cache lines are being used.
+    /// - ConfigError::StoreFailed: cache miss, and the dirty cache line evicted cannot be written back.
+    /// - ConfigError::LoadFailed: cache miss, and the value cannot be loaded.
+    pub(crate) fn get_mut<T: Cacheable + Default>(&self) -> ConfigResult<Arc<CacheLine>> {
+        let mut lines = self.lock();
+        let line = self.load::<T>(&mut lines)?;
+        Self::begin_write::<T>(line)
+    }
+
+    /// Retrieve the cache line of `T` for reading if cached, `None` on cache miss without loading.
+    /// - ConfigError::AlreadyBorrowed: the cache line is being written.
+    #[cfg(feature = "async")]
+    pub(crate) fn get_cached<T: Cacheable>(&self) -> ConfigResult<Option<Arc<CacheLine>>> {
+        let mut lines = self.lock();
+        match Self::hit::<T>(&mut lines) {
+            Some(line) => Self::begin_read::<T>(lines, line).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the cache line of `T` for writing if cached, `None` on cache miss without loading.
+    /// - ConfigError::AlreadyBorrowed: the cache line is being read or written.
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut_cached<T: Cacheable>(&self) -> ConfigResult<Option<Arc<CacheLine>>> {
+        let mut lines = self.lock();
+        Self::hit::<T>(&mut lines)
+            .map(Self::begin_write::<T>)
+            .transpose()
+    }
+
+    /// Take the read flag of `line`, notifying the subscribers after `lines` unlocked if it was changed.
+    fn begin_read<T: Cacheable>(
+        lines: Lines<'_>,
+        line: Arc<CacheLine>,
+    ) -> ConfigResult<Arc<CacheLine>> {
+        if !line.flag.read() {
+            return Err(AlreadyBorrowed {
+                type_name: type_name::<T>(),
+            }
+            .build());
+        }
+        let notify = line.read_to_notify_changed();
+        drop(lines);
+        if notify {
+            line.notify_reading();
+        }
+        Ok(line)
+    }
+
+    /// Take the write flag of `line`.
+    fn begin_write<T: Cacheable>(line: Arc<CacheLine>) -> ConfigResult<Arc<CacheLine>> {
+        line.flag.write().then_some(line).ok_or_else(|| {
+            AlreadyBorrowed {
+                type_name: type_name::<T>(),
+            }
+            .build()
+        })
+    }
+
+    /// Retry `f` until it succeeds or fails with an error other than borrowing,
+    /// waiting for a ref to be released between retries.
+    /// Returns the last error if `timeout` elapsed, waits forever if `timeout` is `None`.
+    pub(crate) fn wait<R>(
+        &self,
+        timeout: Option<Duration>,
+        mut f: impl FnMut() -> ConfigResult<R>,
+    ) -> ConfigResult<R> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let epoch = self.inner.released.epoch();
+            match f() {
+                Err(ConfigError::AlreadyBorrowed { .. } | ConfigError::CacheBusy { .. })
+                    if self.inner.released.wait(epoch, deadline) => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// Write back the cache line of `T` if it is dirty.
+    /// - ConfigError::AlreadyBorrowed: the cache line is being written.
+    /// - ConfigError::StoreFailed: the value cannot be written back.
+    pub(crate) fn flush<T: Cacheable>(&self) -> ConfigResult<()> {
+        let lines = self.lock();
+        let Some(line) = lines
+            .iter()
+            .find(|l| l.type_id == TypeId::of::<T>())
+            .cloned()
+        else {
+            return Ok(());
+        };
+        let notify = line.flush()? && line.read_to_notify();
+        drop(lines);
+        if notify {
+            line.notify_reading();
+        }
+        Ok(())
+    }
+
+    /// Discard the cache line of `T` and load it from the source again.
+    /// - ConfigError::AlreadyBorrowed: the cache line is being read or written.
+    /// - ConfigError::LoadFailed: the value cannot be loaded, the cache line is left untouched.
+    pub(crate) fn reload<T: Cacheable>(&self) -> ConfigResult<()> {
+        let lines = self.lock();
+        let Some(line) = lines
+            .iter()
+            .find(|l| l.type_id == TypeId::of::<T>())
+            .cloned()
+        else {
+            return Ok(());
+        };
+        let value = line.counters.load(T::load).map_err(|e| {
+            from_source_error(e, |e| {
+                LoadFailed {
+                    type_name: line.type_name,
+                }
+                .into_error(e)
+            })
+        })?;
+        line.replace(Box::new(value))?;
+        line.flag.set_clean();
+        let notify = line.read_to_notify();
+        drop(lines);
+        if notify {
+            line.notify_reading();
+        }
+        Ok(())
+    }
+
+    /// Put `value` into the cache as if it was loaded from the source, the cached one is discarded without writing back.
+    /// - ConfigError::AlreadyBorrowed: the cache line is being read or written.
+    /// - ConfigError::CacheBusy: cache miss, and all cache lines are being used.
+    /// - ConfigError::StoreFailed: cache miss, and the dirty cache line evicted cannot be written back.
+    pub(crate) fn seed<T: Cacheable>(&self, value: T) -> ConfigResult<()> {
+        let mut lines = self.lock();
+        let line = match Self::hit::<T>(&mut lines) {
+            Some(line) => {
+                line.replace(Box::new(value))?;
+                line.flag.set_clean();
+                line
+            }
+            None => self.insert(&mut lines, value)?,
+        };
+        line.changed.store(true, Ordering::Relaxed);
+        let notify = line.read_to_notify_changed();
+        drop(lines);
+        if notify {
+            line.notify_reading();
+        }
+        Ok(())
+    }
+
+    /// Get the snapshot of `T`, loading it into cache if not cached.
+    /// - ConfigError::AlreadyBorrowed: there's no snapshot, and the cache line is being written.
+    /// - ConfigError::CacheBusy: cache miss, and all cache lines are being used.
+    /// - ConfigError::StoreFailed: cache miss, and the dirty cache line evicted cannot be written back.
+    /// - ConfigError::LoadFailed: cache miss, and the value cannot be loaded.
+    pub(crate) fn snapshot<T: Cacheable + Default + Clone>(&self) -> ConfigResult<Arc<T>> {
+        let mut lines = self.lock();
+        let line = self.load::<T>(&mut lines)?;
+        let notify = line.read_to_notify_changed();
+        drop(lines);
+        if notify {
+            line.notify_reading();
+        }
+        line.snapshot::<T>().ok_or_else(|| {
+            AlreadyBorrowed {
+                type_name: type_name::<T>(),
+            }
+            .build()
+        })
+    }
+
+    /// Discard the cache line of `T` without writing back.
+    /// - ConfigError::AlreadyBorrowed: the cache line is being read or written.
+    pub(crate) fn invalidate<T: Cacheable>(&self) -> ConfigResult<()> {
+        let mut lines = self.lock();
+        let Some(i) = lines.iter().position(|l| l.type_id == TypeId::of::<T>()) else {
+            return Ok(());
+        };
+        lines[i].ensure_unused()?;
+        lines.remove(i);
+        Ok(())
+    }
+
+    /// Write back all dirty cache lines, errors are collected into `ConfigError::FlushFailed`.
+    pub(crate) fn flush_all(&self) -> ConfigResult<()> {
+        let lines = self.lock();
+        let mut notify = vec![];
+        let mut errors = vec![];
+        for line in lines.iter() {
+            match line.flush() {
+                Ok(true) if line.read_to_notify() => notify.push(line.clone()),
+                Ok(_) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        drop(lines);
+        notify.iter().for_each(|l| l.notify_reading());
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(FlushFailed { errors }.build()),
+        }
+    }
+
+    /// Subscribers of `T`, created if not exists.
+    pub(crate) fn subscribers<T: Cacheable>(&self) -> Arc<Subscribers> {
+        self.inner.subscribers::<T>()
+    }
+
+    /// Counters of each type ever cached.
+    pub(crate) fn counters(&self) -> Vec<Arc<Counters>> {
+        let counters = self
+            .inner
+            .counters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        counters.values().cloned().collect()
+    }
+
+    /// The metadata of each cache line, ordered by LRU.
+    pub(crate) fn entries(&self) -> Vec<CfgEntry> {
+        self.lock().iter().map(|l| l.entry()).collect()
+    }
+
+    /// A weak handle of the cache lines, which does not keep them from being dropped.
+    #[cfg(feature = "watch")]
+    pub(crate) fn downgrade(&self) -> std::sync::Weak<CacheInner> {
+        Arc::downgrade(&self.inner)
+    }
+
+    /// Subscribers of the evictions.
+    pub(crate) fn evictions(&self) -> Arc<Subscribers> {
+        self.inner.evictions.clone()
+    }
+
+    fn lock(&self) -> Lines<'_> {
+        self.inner.lock()
+    }
+
+    /// Load Cacheable into cache line and update LRU, the cache line is moved to the front.
+    fn load<T: Cacheable + Default>(&self, lines: &mut Lines<'_>) -> ConfigResult<Arc<CacheLine>> {
+        match Self::hit::<T>(lines) {
+            Some(line) => {
+                line.counters.hit();
+                Ok(line)
+            }
+            None => {
+                let counters = self.inner.counters::<T>();
+                counters.miss();
+                let value = load_or_default::<T>(&counters)?;
+                self.insert(lines, value)
+            }
+        }
+    }
+
+    /// Load `T` from the source without holding the lock, and insert it if still not cached.
+    /// This is used to keep the blocking load out of the async executor, so a cache line is not returned.
+    /// - ConfigError::CacheBusy: all cache lines are being used.
+    /// - ConfigError::StoreFailed: the dirty cache line evicted cannot be written back.
+    /// - ConfigError::LoadFailed: the value cannot be loaded.
+    #[cfg(feature = "async")]
+    pub(crate) fn preload<T: Cacheable + Default>(&self) -> ConfigResult<()> {
+        if let Some(line) = Self::hit::<T>(&mut self.lock()) {
+            line.counters.hit();
+            return Ok(());
+        }
+        let counters = self.inner.counters::<T>();
+        counters.miss();
+        let value = load_or_default::<T>(&counters)?;
+        let mut lines = self.lock();
+        if Self::hit::<T>(&mut lines).is_none() {
+            self.insert(&mut lines, value)?;
+        }
+        Ok(())
+    }
+
+    /// Update LRU if `T` is cached, the cache line is moved to the front.
+    fn hit<T: Cacheable>(lines: &mut [Arc<CacheLine>]) -> Option<Arc<CacheLine>> {
+        let i = lines.iter().position(|l| l.type_id == TypeId::of::<T>())?;
+        lines[..=i].rotate_right(1);
+        #[cfg(feature = "watch")]
+        if lines[0].stale.load(Ordering::Relaxed) {
+            lines[0].refresh::<T>();
+        }
+        Some(lines[0].clone())
+    }
+
+    /// Insert a new cache line to the front, evicting the least recently used one not being used if full.
+    /// The cache is unbounded if N is 0.
+    fn insert<T: Cacheable>(
+        &self,
+        lines: &mut Lines<'_>,
+        value: T,
+    ) -> ConfigResult<Arc<CacheLine>> {
+        if N != 0 && lines.len() >= N {
+            let i = lines
+                .iter()
+                .rposition(|l| !l.flag.in_using())
+                .ok_or_else(|| {
+                    CacheBusy {
+                        type_name: type_name::<T>(),
+                    }
+                    .build()
+                })?;
+            lines[i].flush()?;
+            let evicted = lines.remove(i);
+            evicted.counters.evict();
+            lines.evicted.push(evicted.type_name);
+        }
+        let line = Arc::new(CacheLine::new(
+            value,
+            self.subscribers::<T>(),
+            self.inner.counters::<T>(),
+            self.inner.released.clone(),
+        ));
+        lines.insert(0, line.clone());
+        Ok(line)
+    }
+}
+
+/// The cache lines locked, the evictions are notified after unlocking,
+/// so that the subscribers can access the cache.
+struct Lines<'a> {
+    lines: Option<MutexGuard<'a, Vec<Arc<CacheLine>>>>,
+    /// Type names of the cache lines evicted.
+    evicted: Vec<&'static str>,
+    evictions: &'a Subscribers,
+}
+
+impl Deref for Lines<'_> {
+    type Target = Vec<Arc<CacheLine>>;
+
+    fn deref(&self) -> &Self::Target {
+        self.lines.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for Lines<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.lines.as_mut().unwrap()
+    }
+}
+
+impl Drop for Lines<'_> {
+    fn drop(&mut self) {
+        drop(self.lines.take());
+        for type_name in self.evicted.drain(..) {
+            self.evictions.notify(&CfgEvicted { type_name });
+        }
+    }
+}
+
+/// A cache line holding one [`Cacheable`].
+pub(crate) struct CacheLine {
+    type_id: TypeId,
+    type_name: &'static str,
+    kind: SourceKind,
+    path: Option<PathBuf>,
+    keyring_entry: Option<&'static str>,
+    pub(crate) flag: Flag,
+    value: UnsafeCell<Box<dyn Cacheable>>,
+    subscribers: Arc<Subscribers>,
+    pub(crate) counters: Arc<Counters>,
+    released: Arc<Released>,
+    /// The value was changed but the subscribers have not been notified yet.
+    changed: AtomicBool,
+    /// The immutable snapshot of the value, cleared once the value is changed.
+    snapshot: Mutex<Option<Arc<dyn Any + Send + Sync>>>,
+    /// The source was changed while the cache line was being used.
+    #[cfg(feature = "watch")]
+    stale: AtomicBool,
+}
+
+/// Load `T` from the source, falling back to the default value if [`Cacheable::fallback()`] allows.
+/// - ConfigError::LoadFailed: the value cannot be loaded, and not allowed to fall back.
+fn load_or_default<T: Cacheable + Default>(counters: &Counters) -> ConfigResult<T> {
+    match counters.load(T::load) {
+        Ok(value) => Ok(value),
+        Err(e) if T::fallback().allows(&e) => Ok(T::default()),
+        Err(e) => Err(from_source_error(e, |e| {
+            LoadFailed {
+                type_name: type_name::<T>(),
+            }
+            .into_error(e)
+        })),
+    }
+}
+
+/// # Safety
+/// The value is only accessed through the refs guarded by `Flag`, or under the lock of `Cache`
+/// when it is not being written. It's only replaced with the write flag held.
+unsafe impl Sync for CacheLine {}
+
+impl CacheLine {
+    fn new<T: Cacheable>(
+        value: T,
+        subscribers: Arc<Subscribers>,
+        counters: Arc<Counters>,
+        released: Arc<Released>,
+    ) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            kind: T::source_kind(),
+            path: T::source_path(),
+            keyring_entry: T::keyring_entry(),
+            flag: Flag::default(),
+            value: UnsafeCell::new(Box::new(value)),
+            subscribers,
+            counters,
+            released,
+            changed: Default::default(),
+            snapshot: Default::default(),
+            #[cfg(feature = "watch")]
+            stale: Default::default(),
+        }
+    }
+
+    /// # Safety
+    /// The caller must ensure the value is not being written.
+    pub(crate) unsafe fn value(&self) -> &dyn Cacheable {
+        unsafe { &**self.value.get() }
+    }
+
+    /// # Safety
+    /// The caller must hold the write flag of this cache line.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn value_mut(&self) -> &mut dyn Cacheable {
+        unsafe { &mut **self.value.get() }
+    }
+
+    /// Replace the value with the write flag held, so that no ref can be taken until the old value is dropped,
+    /// including the ones taken without the lock of `Cache` (e.g. for snapshotting).
+    /// - ConfigError::AlreadyBorrowed: the cache line is being read or written.
+    fn replace(&self, value: Box<dyn Cacheable>) -> ConfigResult<()> {
+        if !self.flag.write() {
+            return Err(AlreadyBorrowed {
+                type_name: self.type_name,
+            }
+            .build());
+        }
+        // Safety: the write flag is held.
+        unsafe { *self.value.get() = value };
+        self.clear_snapshot();
+        self.flag.end_write();
+        self.released.notify();
+        Ok(())
+    }
+
+    /// Get the snapshot of the value, which is cloned only if the value was changed since last snapshot.
+    /// Returns `None` if there's no snapshot and the value is being written.
+    pub(crate) fn snapshot<T: Cacheable + Clone>(&self) -> Option<Arc<T>> {
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        if snapshot.is_none() {
+            if !self.flag.read() {
+                return None;
+            }
+            // Safety: the read flag is held.
+            let value = unsafe { self.value() }
+                .as_any()
+                .downcast_ref::<T>()
+                .cloned();
+            self.end_read();
+            *snapshot = Some(Arc::new(value.expect("downcast failed")));
+        }
+        snapshot
+            .clone()
+            .map(|s| s.downcast().expect("downcast failed"))
+    }
+
+    fn clear_snapshot(&self) {
+        *self.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    fn entry(&self) -> CfgEntry {
+        CfgEntry {
+            type_name: self.type_name,
+            kind: self.kind,
+            path: self.path.clone(),
+            keyring_entry: self.keyring_entry,
+            dirty: self.flag.is_dirty(),
+            borrow: self.flag.borrow_state(),
+        }
+    }
+
+    /// Ensure no ref of this cache line is alive. The caller must hold the lock of `Cache`.
+    fn ensure_unused(&self) -> ConfigResult<()> {
+        match self.flag.in_using() {
+            true => Err(AlreadyBorrowed {
+                type_name: self.type_name,
+            }
+            .build()),
+            false => Ok(()),
+        }
+    }
+
+    /// Load the value from the changed source. The caller must hold the lock of `Cache`.
+    /// - Dirty: the changes in memory win, and will overwrite the source when written back.
+    /// - Being used: marked stale, and refreshed on next retrieving.
+    /// - Failed to load: the value in memory is kept.
+    #[cfg(feature = "watch")]
+    fn refresh<T: Cacheable>(&self) {
+        if self.flag.is_dirty() {
+            self.stale.store(false, Ordering::Relaxed);
+            return;
+        }
+        if self.flag.in_using() {
+            self.stale.store(true, Ordering::Relaxed);
+            return;
+        }
+        self.stale.store(false, Ordering::Relaxed);
+        if let Ok(value) = self.counters.load(T::refresh) {
+            match self.replace(Box::new(value)) {
+                Ok(()) => self.changed.store(true, Ordering::Relaxed),
+                // Taken by a ref without the lock since checked.
+                Err(_) => self.stale.store(true, Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Mark the value as dirty and changed, since it's mutably dereferenced.
+    pub(crate) fn set_dirty(&self) {
+        self.flag.set_dirty();
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Mark the value as clean but changed, since it's written back by others.
+    pub(crate) fn set_stored(&self) {
+        self.flag.set_clean();
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Release the write flag. If the value was changed, the snapshot is cleared and the subscribers are notified.
+    /// The snapshot is cleared after the write flag released, so that snapshots are always available.
+    pub(crate) fn end_write(&self) {
+        let changed = self.changed.load(Ordering::Relaxed);
+        if changed && !self.subscribers.is_empty() {
+            self.flag.downgrade();
+            self.clear_snapshot();
+            self.notify_reading();
+        } else {
+            self.flag.end_write();
+            if changed {
+                self.clear_snapshot();
+            }
+            self.released.notify();
+        }
+    }
+
+    /// Release the read flag.
+    pub(crate) fn end_read(&self) {
+        self.flag.end_read();
+        self.released.notify();
+    }
+
+    /// Take the read flag to notify the subscribers with the current value, returns false if there's no subscriber or being written.
+    /// The caller must hold the lock of `Cache`, so that the value cannot be replaced before the read flag taken,
+    /// and call [`CacheLine::notify_reading()`] after releasing the lock if true, since the subscribers may retrieve from the cache.
+    fn read_to_notify(&self) -> bool {
+        !self.subscribers.is_empty() && self.flag.read()
+    }
+
+    /// Same as [`CacheLine::read_to_notify()`], but only if the value was changed and the subscribers have not been notified yet.
+    /// Returns false if being written, the change is left for [`CacheLine::end_write()`].
+    fn read_to_notify_changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed) && self.flag.read()
+    }
+
+    /// Notify the subscribers with the current value, the read flag held by the caller is released after notifying.
+    fn notify_reading(&self) {
+        struct EndRead<'a>(&'a CacheLine);
+        impl Drop for EndRead<'_> {
+            fn drop(&mut self) {
+                self.0.end_read();
+            }
+        }
+        let _end_read = EndRead(self);
+        self.changed.store(false, Ordering::Relaxed);
+        // Safety: the read flag is held.
+        self.subscribers.notify(unsafe { self.value() }.as_any());
+    }
+
+    /// Write back the value if dirty, returns whether written. The caller must hold the lock of `Cache`.
+    fn flush(&self) -> ConfigResult<bool> {
+        if !self.flag.is_dirty() {
+            return Ok(false);
+        }
+        if self.flag.is_writing() {
+            return Err(AlreadyBorrowed {
+                type_name: self.type_name,
+            }
+            .build());
+        }
+        // Safety: not being written, and no new writer can come in without the lock.
+        match self.counters.store(|| unsafe { self.value() }.store()) {
+            Ok(()) => {
+                self.flag.set_clean();
+                Ok(true)
+            }
+            Err(e) => Err(from_source_error(e, |e| {
+                StoreFailed {
+                    type_name: self.type_name,
+                }
+                .into_error(e)
+            })),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Flag {
+    // 000...00
+    //        ^ write
+    //  ^^^^^^ read count
+    // ^ dirty
+    inner: AtomicUsize,
+}
+
+impl Flag {
+    const WRITE: usize = 1;
+    const READ: usize = 2;
+    const DIRTY: usize = !(usize::MAX >> 1);
+
+    /// Mark as writing, returns false if being read or written.
+    fn write(&self) -> bool {
+        self.inner
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |f| {
+                (f & !Self::DIRTY == 0).then_some(f | Self::WRITE)
+            })
+            .is_ok()
+    }
+
+    /// Increase the read count, returns false if being written.
+    fn read(&self) -> bool {
+        self.inner
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |f| {
+                (f & Self::WRITE == 0).then_some(f + Self::READ)
+            })
+            .is_ok()
+    }
+
+    fn end_write(&self) {
+        self.inner.fetch_and(!Self::WRITE, Ordering::Release);
+    }
+
+    /// Turn the write flag into a read flag.
+    fn downgrade(&self) {
+        self.inner
+            .fetch_add(Self::READ - Self::WRITE, Ordering::Release);
+    }
+
+    fn end_read(&self) {
+        self.inner.fetch_sub(Self::READ, Ordering::Release);
+    }
+
+    fn set_dirty(&self) {
+        self.inner.fetch_or(Self::DIRTY, Ordering::Relaxed);
+    }
+
+    fn set_clean(&self) {
+        self.inner.fetch_and(!Self::DIRTY, Ordering::Relaxed);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.inner.load(Ordering::Relaxed) & Self::DIRTY != 0
+    }
+
+    fn is_writing(&self) -> bool {
+        self.inner.load(Ordering::Acquire) & Self::WRITE != 0
+    }
+
+    fn in_using(&self) -> bool {
+        self.inner.load(Ordering::Acquire) & !Self::DIRTY != 0
+    }
+
+    fn borrow_state(&self) -> BorrowState {
+        match self.inner.load(Ordering::Acquire) & !Self::DIRTY {
+            0 => BorrowState::Unused,
+            f if f & Self::WRITE != 0 => BorrowState::Writing,
+            f => BorrowState::Reading(f / Self::READ),
+        }
+    }
+}
+
+/// Wakes up the threads waiting for a ref to be released.
+/// The epoch is bumped on each release, so that a release between retrying and waiting is not missed.
+#[derive(Default)]
+pub(crate) struct Released {
+    state: Mutex<ReleasedState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct ReleasedState {
+    epoch: u64,
+    waiters: usize,
+}
+
+impl Released {
+    fn lock(&self) -> MutexGuard<'_, ReleasedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn epoch(&self) -> u64 {
+        self.lock().epoch
+    }
+
+    fn notify(&self) {
+        let mut state = self.lock();
+        state.epoch = state.epoch.wrapping_add(1);
+        if state.waiters != 0 {
+            self.cond.notify_all();
+        }
+    }
+
+    /// Wait until a ref released since `epoch`, returns false if `deadline` reached.
+    fn wait(&self, epoch: u64, deadline: Option<Instant>) -> bool {
+        let mut state = self.lock();
+        state.waiters += 1;
+        while state.epoch == epoch {
+            state = match deadline {
+                None => self.cond.wait(state).unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if !timeout.is_zero() => {
+                        self.cond
+                            .wait_timeout(state, timeout)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0
+                    }
+                    _ => break,
+                },
+            };
+        }
+        state.waiters -= 1;
+        state.epoch != epoch
+    }
+}
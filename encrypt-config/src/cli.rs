@@ -0,0 +1,184 @@
+//! # Cli
+//! Override the persisted values by command line arguments for this run only, implemented through [clap](https://crates.io/crates/clap).
+//!
+//! The overrides of a type are recorded process-wide, then applied each time the `PersistSource` or `SecretSource`
+//! derived with `#[source(cli)]` is loaded, and restored to the values in the file when written back.
+//! So record them at startup before the type is retrieved, or [`Config::reload()`](crate::Config::reload) it afterward.
+//!
+//! # Example
+//! ```no_run
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use encrypt_config::{CliSource, Config, PersistSource};
+//! # use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Default, Serialize, Deserialize, PersistSource, CliSource)]
+//! #[cfg_attr(feature = "default_config_dir", source(name = "app.json", cli))]
+//! #[cfg_attr(not(feature = "default_config_dir"), source(path = "/path/to/app.json", cli))]
+//! struct AppConfig {
+//!     /// The log level.
+//!     log_level: String,
+//! }
+//!
+//! // myapp --log-level debug
+//! let matches = AppConfig::command(encrypt_config::cli::clap::Command::new("myapp")).get_matches();
+//! AppConfig::set_overrides(&matches).unwrap();
+//! let cfg: Config<1> = Config::new();
+//! assert_eq!(cfg.get::<AppConfig>().log_level, "debug");
+//! # }
+//! ```
+
+use crate::{env::set_raw, layered::merge};
+pub use clap;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    io,
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+/// A config type whose fields can be overridden by command line arguments.
+/// This is implemented by the derive macro `CliSource`, generating a long flag for each field.
+pub trait CliSource: Serialize + DeserializeOwned + Default + 'static {
+    /// The args of the fields, e.g. `--log-level <VALUE>` for field `log_level`.
+    /// Flags of `bool` fields can omit the value, e.g. `--verbose`.
+    fn args() -> Vec<clap::Arg>;
+
+    /// Add the args of the fields to `cmd`.
+    fn command(cmd: clap::Command) -> clap::Command {
+        cmd.args(Self::args())
+    }
+
+    /// Record the values given in `matches` as the overrides of this type,
+    /// each value is parsed as json, or taken as a string if it's not json or the field does not accept it.
+    ///
+    /// # Errors
+    /// - [`io::ErrorKind::InvalidData`]: a value cannot be parsed into the field type, the flag is named in the message.
+    fn set_overrides(matches: &clap::ArgMatches) -> io::Result<()> {
+        let mut overrides = Value::Object(Map::new());
+        for arg in Self::args() {
+            let id = arg.get_id().as_str();
+            let Ok(Some(raw)) = matches.try_get_one::<String>(id) else {
+                continue;
+            };
+            let mut value = serde_json::to_value(Self::default())?;
+            set_raw::<Self>(&mut value, std::iter::once(id), raw).map_err(|e| {
+                let flag = arg.get_long().unwrap_or(id);
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid argument `--{flag} {raw}`: {e}"),
+                )
+            })?;
+            if let Some(v) = value.get(id) {
+                merge(
+                    &mut overrides,
+                    Value::Object(Map::from_iter([(id.into(), v.clone())])),
+                );
+            }
+        }
+        set_overrides_value::<Self>(overrides);
+        Ok(())
+    }
+}
+
+/// Record the fields of a parsed `clap` struct as the overrides of `T`, the `None`s are skipped.
+/// The field names of `args` should be the same as `T`'s.
+///
+/// # Errors
+/// - [`io::ErrorKind::InvalidData`]: the fields cannot be deserialized into the ones of `T`.
+pub fn set_overrides_from<T, A>(args: &A) -> io::Result<()>
+where
+    T: Serialize + DeserializeOwned + Default + 'static,
+    A: Serialize,
+{
+    let overrides = strip_nulls(serde_json::to_value(args)?);
+    let mut value = serde_json::to_value(T::default())?;
+    merge(&mut value, overrides.clone());
+    serde_json::from_value::<T>(value).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid command line arguments: {e}"),
+        )
+    })?;
+    set_overrides_value::<T>(overrides);
+    Ok(())
+}
+
+/// Remove the overrides of `T` recorded.
+pub fn clear_overrides<T: 'static>() {
+    overrides().remove(&TypeId::of::<T>());
+}
+
+/// Apply the overrides of `T` recorded on `value`.
+pub fn overlay<T>(value: T) -> io::Result<T>
+where
+    T: Serialize + DeserializeOwned + 'static,
+{
+    let Some(overrides) = overrides().get(&TypeId::of::<T>()).cloned() else {
+        return Ok(value);
+    };
+    let mut value = serde_json::to_value(value)?;
+    merge(&mut value, overrides);
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Restore the fields overridden of `value` to the ones in `base`, so that the overrides are never written back.
+/// The fields absent in `base` are removed.
+pub fn without_overlay<T>(value: &T, base: &T) -> io::Result<T>
+where
+    T: Serialize + DeserializeOwned + 'static,
+{
+    let Some(overrides) = overrides().get(&TypeId::of::<T>()).cloned() else {
+        return Ok(serde_json::from_value(serde_json::to_value(value)?)?);
+    };
+    let base = serde_json::to_value(base)?;
+    let mut value = serde_json::to_value(value)?;
+    restore(&mut value, &base, &overrides);
+    Ok(serde_json::from_value(value)?)
+}
+
+fn set_overrides_value<T: 'static>(value: Value) {
+    overrides().insert(TypeId::of::<T>(), value);
+}
+
+fn overrides() -> MutexGuard<'static, HashMap<TypeId, Value>> {
+    static OVERRIDES: OnceLock<Mutex<HashMap<TypeId, Value>>> = OnceLock::new();
+    OVERRIDES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Restore the leaves of `overrides` in `value` to the ones in `base`, the ones absent in `base` are removed.
+fn restore(value: &mut Value, base: &Value, overrides: &Value) {
+    let (Value::Object(value), Value::Object(overrides)) = (value, overrides) else {
+        return;
+    };
+    for (key, o) in overrides {
+        let Some(b) = base.get(key) else {
+            value.remove(key);
+            continue;
+        };
+        let Some(v) = value.get_mut(key) else {
+            continue;
+        };
+        match o {
+            Value::Object(_) => restore(v, b, o),
+            _ => *v = b.clone(),
+        }
+    }
+}
+
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
@@ -1,8 +1,18 @@
 //! # Config
 //! This module provides a [`Config`] struct that can be used to cache configuration values.
 
-use rom_cache::cache::{CacheMut, CacheRef};
-use std::any::Any;
+use crate::{
+    cache::{Cache, CacheLine},
+    error::ConfigResult,
+    stats::Counters,
+};
+use std::{
+    any::Any,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 /// A struct that can be used to **cache** configuration values.
 /// This behaves like a native cache in CPU:
@@ -14,9 +24,14 @@ use std::any::Any;
 /// `get_mut`
 /// 1. If cache hit, returns the cached value's mut ref, dereferencing it will mark the value as dirty.
 /// 2. If cache miss, loads the value from the source to cache (default as fallback), then returns the mut ref.
-/// 3. All cached values marked dirty will be written back when Config dropped or cache line evicted.
+/// 3. All cached values marked dirty will be written back when Config dropped, cache line evicted or flushed.
+///
+/// **At most N** different config types can be cached at the same time due to the cache capacity.
+/// When full, the least recently used one not being used is evicted (written back if dirty),
+/// which is reported to the callbacks of [`Config::on_evict()`], and counted in
+/// [`CfgStats::evictions`](crate::CfgStats::evictions) of [`Config::stats()`].
+/// `Config<0>` (aka [`DynConfig`]) is unbounded, which never evicts.
 ///
-/// **At most N** different config types are safe to be managed at the same time due to the cache capacity.
 /// And each type can be ref **up to (usize::MAX >> 2)** times or mut ref **up to 1** time at the same time.
 /// Or invalid borrow may happen (since the counter wraps around on overflow).
 #[cfg_attr(
@@ -24,14 +39,52 @@ use std::any::Any;
     doc = "To avoid entering the password during testing, you can enable `mock` feature. This can always return the **same** Encrypter during **each** test."
 )]
 pub struct Config<const N: usize> {
-    cache: rom_cache::Cache<1, N>,
+    pub(crate) cache: Cache<N>,
+}
+
+/// A [`Config`] growing as needed, which never evicts.
+pub type DynConfig = Config<0>;
+
+/// The process-wide [`DynConfig`], which is lazily initialized on first access.
+///
+/// Share it instead of building a [`Config`] in each crate, since two [`Config`]s managing
+/// the same source would overwrite each other's changes when written back.
+///
+/// Caution: statics are never dropped, so dirty values are not written back automatically.
+/// Call `global().flush_all()` at shutdown.
+pub fn global() -> &'static DynConfig {
+    static GLOBAL: OnceLock<DynConfig> = OnceLock::new();
+    GLOBAL.get_or_init(DynConfig::default)
+}
+
+/// A builder of [`Config`] seeded with runtime values, see [`Config::builder()`].
+pub struct ConfigBuilder<const N: usize> {
+    config: Config<N>,
+}
+
+impl<const N: usize> ConfigBuilder<N> {
+    /// Seed `value`, see [`Config::insert()`].
+    /// Seed at most N types, or the earlier ones are evicted.
+    pub fn with<T>(self, value: T) -> Self
+    where
+        T: crate::source::Cacheable,
+    {
+        // Never fails, since the seeded values are neither used nor dirty.
+        self.config.insert(value).unwrap_or_else(|e| panic!("{e}"));
+        self
+    }
+
+    /// Build the [`Config`].
+    pub fn build(self) -> Config<N> {
+        self.config
+    }
 }
 
 impl<const N: usize> Default for Config<N> {
     /// Create an empty [`Config`] cache.
     fn default() -> Self {
         Self {
-            cache: rom_cache::Cache::<1, N>::default(),
+            cache: Cache::default(),
         }
     }
 }
@@ -42,12 +95,34 @@ impl<const N: usize> Config<N> {
         Self::default()
     }
 
+    /// Create a [`ConfigBuilder`] to seed values at startup, e.g. values from CLI arguments.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "derive")]
+    /// # {
+    /// # use encrypt_config::{DynConfig, NormalSource};
+    /// #[derive(Default, NormalSource)]
+    /// struct Args {
+    ///     verbose: bool,
+    /// }
+    /// let cfg = DynConfig::builder().with(Args { verbose: true }).build();
+    /// assert!(cfg.get::<Args>().verbose);
+    /// # }
+    /// ```
+    pub fn builder() -> ConfigBuilder<N> {
+        ConfigBuilder {
+            config: Self::default(),
+        }
+    }
+
     /// Get an immutable ref ([`CfgRef`]) from the config.
-    /// If the value was not valid, it would try loading from source, and fell back to the default value.
+    /// If the value was not valid, it would try loading from source, and fell back to the default value as [`Cacheable::fallback()`](crate::source::Cacheable::fallback) allows.
     ///
     /// Caution: You can only get up to (usize::MAX >> 2) immutable refs ([`CfgRef`]) of each type at the same time.
     ///
     /// If the value was marked as writing, it would panic like `RefCell`.
+    /// It also panics if the value cannot be loaded, use [`Config::try_get()`] to handle the error instead.
     /// See [`CfgRef`] for more details.
     pub fn get<T>(&self) -> <T as Cacheable<()>>::Ref<'_>
     where
@@ -57,11 +132,12 @@ impl<const N: usize> Config<N> {
     }
 
     /// Get a mutable ref ([`CfgMut`]) from the config.
-    /// If the value was not valid, it would try loading from source, and fell back to the default value.
+    /// If the value was not valid, it would try loading from source, and fell back to the default value as [`Cacheable::fallback()`](crate::source::Cacheable::fallback) allows.
     ///
     /// Caution: You can only get up to 1 mutable ref ([`CfgMut`]) of each type at the same time.
     ///
     /// If the value was marked as reading or writing, it would panic like `RefCell`.
+    /// It also panics if the value cannot be loaded, use [`Config::try_get_mut()`] to handle the error instead.
     /// See [`CfgMut`] for more details.
     pub fn get_mut<T>(&self) -> <T as Cacheable<()>>::Mut<'_>
     where
@@ -70,11 +146,100 @@ impl<const N: usize> Config<N> {
         T::retrieve_mut(&self.cache)
     }
 
+    /// Try to get an immutable ref ([`CfgRef`]) from the config.
+    /// This is the fallible version of [`Config::get()`].
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): the value was marked as writing.
+    /// - [`ConfigError::CacheBusy`](crate::error::ConfigError::CacheBusy): cache miss, and all cache lines are being used.
+    /// - [`ConfigError::StoreFailed`](crate::error::ConfigError::StoreFailed): cache miss, and the dirty value evicted cannot be written back.
+    /// - [`ConfigError::LoadFailed`](crate::error::ConfigError::LoadFailed): cache miss, and the value cannot be loaded nor fall back to the default one.
+    pub fn try_get<T>(&self) -> ConfigResult<<T as Cacheable<()>>::Ref<'_>>
+    where
+        T: Cacheable<()> + Any + Send + Sync,
+    {
+        T::try_retrieve(&self.cache)
+    }
+
+    /// Try to get a mutable ref ([`CfgMut`]) from the config.
+    /// This is the fallible version of [`Config::get_mut()`].
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): the value was marked as reading or writing.
+    /// - [`ConfigError::CacheBusy`](crate::error::ConfigError::CacheBusy): cache miss, and all cache lines are being used.
+    /// - [`ConfigError::StoreFailed`](crate::error::ConfigError::StoreFailed): cache miss, and the dirty value evicted cannot be written back.
+    /// - [`ConfigError::LoadFailed`](crate::error::ConfigError::LoadFailed): cache miss, and the value cannot be loaded nor fall back to the default one.
+    pub fn try_get_mut<T>(&self) -> ConfigResult<<T as Cacheable<()>>::Mut<'_>>
+    where
+        T: Cacheable<()> + Any + Send + Sync,
+    {
+        T::try_retrieve_mut(&self.cache)
+    }
+
+    /// Get an immutable ref ([`CfgRef`]) from the config, blocking the current thread until the value is not being written.
+    /// So that [`Config`] can be shared across threads like a `RwLock`.
+    ///
+    /// Caution: Getting while holding a [`CfgMut`] of the same type in the current thread will deadlock.
+    ///
+    /// # Panic
+    /// - If failed for reasons other than borrowing, see [`Config::try_get()`].
+    pub fn get_blocking<T>(&self) -> <T as Cacheable<()>>::Ref<'_>
+    where
+        T: Cacheable<()> + Any + Send + Sync,
+    {
+        self.cache
+            .wait(None, || T::try_retrieve(&self.cache))
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Get a mutable ref ([`CfgMut`]) from the config, blocking the current thread until the value is not being read or written.
+    /// So that [`Config`] can be shared across threads like a `RwLock`.
+    ///
+    /// Caution: Getting while holding a [`CfgRef`] or [`CfgMut`] of the same type in the current thread will deadlock.
+    ///
+    /// # Panic
+    /// - If failed for reasons other than borrowing, see [`Config::try_get_mut()`].
+    pub fn get_mut_blocking<T>(&self) -> <T as Cacheable<()>>::Mut<'_>
+    where
+        T: Cacheable<()> + Any + Send + Sync,
+    {
+        self.cache
+            .wait(None, || T::try_retrieve_mut(&self.cache))
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Try to get an immutable ref ([`CfgRef`]) from the config, waiting at most `timeout` for the value not being written.
+    ///
+    /// # Errors
+    /// - The errors of [`Config::try_get()`], borrowing errors are returned only if `timeout` elapsed.
+    pub fn get_timeout<T>(&self, timeout: Duration) -> ConfigResult<<T as Cacheable<()>>::Ref<'_>>
+    where
+        T: Cacheable<()> + Any + Send + Sync,
+    {
+        self.cache
+            .wait(Some(timeout), || T::try_retrieve(&self.cache))
+    }
+
+    /// Try to get a mutable ref ([`CfgMut`]) from the config, waiting at most `timeout` for the value not being read or written.
+    ///
+    /// # Errors
+    /// - The errors of [`Config::try_get_mut()`], borrowing errors are returned only if `timeout` elapsed.
+    pub fn get_mut_timeout<T>(
+        &self,
+        timeout: Duration,
+    ) -> ConfigResult<<T as Cacheable<()>>::Mut<'_>>
+    where
+        T: Cacheable<()> + Any + Send + Sync,
+    {
+        self.cache
+            .wait(Some(timeout), || T::try_retrieve_mut(&self.cache))
+    }
+
     /// Get many immutable refs from the config.
     ///
     /// T: (T1, T2, T3,)
     ///
-    /// If the value was not valid, it would try loading from source, and fell back to the default value.
+    /// If the value was not valid, it would try loading from source, and fell back to the default value as [`Cacheable::fallback()`](crate::source::Cacheable::fallback) allows.
     /// See [`CfgRef`] for more details.
     pub fn get_many<T>(&self) -> <T as Cacheable<((),)>>::Ref<'_>
     where
@@ -87,7 +252,7 @@ impl<const N: usize> Config<N> {
     ///
     /// T: (T1, T2, T3,)
     ///
-    /// If the value was not valid, it would try loading from source, and fell back to the default value.
+    /// If the value was not valid, it would try loading from source, and fell back to the default value as [`Cacheable::fallback()`](crate::source::Cacheable::fallback) allows.
     /// See [`CfgMut`] for more details.
     pub fn get_mut_many<T>(&self) -> <T as Cacheable<((),)>>::Mut<'_>
     where
@@ -95,15 +260,237 @@ impl<const N: usize> Config<N> {
     {
         T::retrieve_mut(&self.cache)
     }
+
+    /// Try to get many immutable refs from the config.
+    /// This is the fallible version of [`Config::get_many()`].
+    ///
+    /// T: (T1, T2, T3,)
+    ///
+    /// Refs already retrieved will be released if any of them failed.
+    /// See [`Config::try_get()`] for the errors.
+    pub fn try_get_many<T>(&self) -> ConfigResult<<T as Cacheable<((),)>>::Ref<'_>>
+    where
+        T: Cacheable<((),)> + Any + Send + Sync,
+    {
+        T::try_retrieve(&self.cache)
+    }
+
+    /// Try to get many mutable refs from the config.
+    /// This is the fallible version of [`Config::get_mut_many()`].
+    ///
+    /// T: (T1, T2, T3,)
+    ///
+    /// Refs already retrieved will be released if any of them failed.
+    /// See [`Config::try_get_mut()`] for the errors.
+    pub fn try_get_mut_many<T>(&self) -> ConfigResult<<T as Cacheable<((),)>>::Mut<'_>>
+    where
+        T: Cacheable<((),)> + Any + Send + Sync,
+    {
+        T::try_retrieve_mut(&self.cache)
+    }
+
+    /// Get an immutable snapshot of `T`, which is not borrowed from the config.
+    /// So it can be sent to other threads or held across `.await`, without blocking [`Config::get_mut()`].
+    ///
+    /// This is copy-on-write: the value is cloned only if it was changed since last snapshot.
+    /// Holders keep the old version consistently, while a writer commits a new one.
+    /// Snapshots are updated once the [`CfgMut`] changing the value is released.
+    ///
+    /// # Panic
+    /// - If [`Config::try_snapshot()`] failed.
+    pub fn snapshot<T>(&self) -> Arc<T>
+    where
+        T: crate::source::Cacheable + Default + Clone,
+    {
+        self.try_snapshot::<T>().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Try to get an immutable snapshot of `T`.
+    /// This is the fallible version of [`Config::snapshot()`].
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): there's no snapshot yet, and a [`CfgMut`] of `T` is alive.
+    /// - [`ConfigError::CacheBusy`](crate::error::ConfigError::CacheBusy): cache miss, and all cache lines are being used.
+    /// - [`ConfigError::StoreFailed`](crate::error::ConfigError::StoreFailed): cache miss, and the dirty value evicted cannot be written back.
+    /// - [`ConfigError::LoadFailed`](crate::error::ConfigError::LoadFailed): cache miss, and the value cannot be loaded nor fall back to the default one.
+    pub fn try_snapshot<T>(&self) -> ConfigResult<Arc<T>>
+    where
+        T: crate::source::Cacheable + Default + Clone,
+    {
+        self.cache.snapshot::<T>()
+    }
+
+    /// Write back the value of `T` immediately if it is cached and marked dirty.
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): a [`CfgMut`] of `T` is alive.
+    /// - [`ConfigError::StoreFailed`](crate::error::ConfigError::StoreFailed): the value cannot be written back, it's still marked dirty.
+    pub fn flush<T>(&self) -> ConfigResult<()>
+    where
+        T: crate::source::Cacheable,
+    {
+        self.cache.flush::<T>()
+    }
+
+    /// Discard the cached value of `T`, and load it from the source again.
+    /// Changes not written back will be lost. Nothing happens if `T` is not cached.
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): a [`CfgRef`] or [`CfgMut`] of `T` is alive.
+    /// - [`ConfigError::LoadFailed`](crate::error::ConfigError::LoadFailed): the value cannot be loaded, the cached value is kept.
+    pub fn reload<T>(&self) -> ConfigResult<()>
+    where
+        T: crate::source::Cacheable,
+    {
+        self.cache.reload::<T>()
+    }
+
+    /// Put `value` into the config as if it was loaded from the source, e.g. values from CLI arguments.
+    /// The cached value of `T` is discarded without writing back, and `value` is not written back unless changed later.
+    /// Use [`Config::replace()`] instead if `value` should be written back.
+    ///
+    /// Caution: If `T` is evicted due to the capacity, it will be loaded from the source on next access.
+    /// Consider [`DynConfig`] for the values without a backing source.
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): a [`CfgRef`] or [`CfgMut`] of `T` is alive.
+    /// - [`ConfigError::CacheBusy`](crate::error::ConfigError::CacheBusy): `T` is not cached, and all cache lines are being used.
+    /// - [`ConfigError::StoreFailed`](crate::error::ConfigError::StoreFailed): `T` is not cached, and the dirty value evicted cannot be written back.
+    pub fn insert<T>(&self, value: T) -> ConfigResult<()>
+    where
+        T: crate::source::Cacheable,
+    {
+        self.cache.seed(value)
+    }
+
+    /// Replace the value of `T` with `value`, returning the old one.
+    /// This is the same as replacing through [`Config::get_mut()`], so the value is marked as dirty.
+    ///
+    /// # Panic
+    /// - If [`Config::try_replace()`] failed.
+    pub fn replace<T>(&self, value: T) -> T
+    where
+        T: crate::source::Cacheable + Default,
+    {
+        self.try_replace(value).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Try to replace the value of `T` with `value`, returning the old one.
+    /// This is the fallible version of [`Config::replace()`].
+    ///
+    /// # Errors
+    /// - The same as [`Config::try_get_mut()`].
+    pub fn try_replace<T>(&self, value: T) -> ConfigResult<T>
+    where
+        T: crate::source::Cacheable + Default,
+    {
+        let mut guard = self.try_get_mut::<T>()?;
+        Ok(std::mem::replace(&mut *guard, value))
+    }
+
+    /// Discard the cached value of `T` without writing back, it will be loaded from the source on next access.
+    /// Changes not written back will be lost.
+    ///
+    /// # Errors
+    /// - [`ConfigError::AlreadyBorrowed`](crate::error::ConfigError::AlreadyBorrowed): a [`CfgRef`] or [`CfgMut`] of `T` is alive.
+    pub fn invalidate<T>(&self) -> ConfigResult<()>
+    where
+        T: crate::source::Cacheable,
+    {
+        self.cache.invalidate::<T>()
+    }
+
+    /// Write back all the cached values marked dirty immediately.
+    ///
+    /// # Errors
+    /// - [`ConfigError::FlushFailed`](crate::error::ConfigError::FlushFailed): lists the types failed and why, see [`Config::flush()`].
+    ///   Values failed are still marked dirty, and others are written back.
+    pub fn flush_all(&self) -> ConfigResult<()> {
+        self.cache.flush_all()
+    }
 }
 
+/// An immutable ref of the config value.
+///
 /// # Panic
 /// - If you already held a [`CfgMut`], [`Config::get()`] will panic.
-pub type CfgRef<'a, T> = CacheRef<'a, T>;
+pub struct CfgRef<'a, T> {
+    pub(crate) line: Arc<CacheLine>,
+    pub(crate) _phantom: PhantomData<&'a T>,
+}
+
+impl<T: Any> Deref for CfgRef<'_, T> {
+    type Target = T;
 
+    fn deref(&self) -> &Self::Target {
+        // Safety: the read flag is held.
+        unsafe { self.line.value() }
+            .as_any()
+            .downcast_ref::<T>()
+            .expect("downcast failed")
+    }
+}
+
+impl<T> Drop for CfgRef<'_, T> {
+    fn drop(&mut self) {
+        self.line.end_read();
+    }
+}
+
+/// A mutable ref of the config value, dereferencing it mutably will mark the value as dirty.
+///
 /// # Panic
 /// - If you already held a [`CfgRef`] or [`CfgMut`], [`Config::get_mut()`] will panic.
-pub type CfgMut<'a, T> = CacheMut<'a, T>;
+pub struct CfgMut<'a, T> {
+    pub(crate) line: Arc<CacheLine>,
+    pub(crate) _phantom: PhantomData<&'a mut T>,
+}
+
+impl<T: Any> Deref for CfgMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the write flag is held.
+        unsafe { self.line.value() }
+            .as_any()
+            .downcast_ref::<T>()
+            .expect("downcast failed")
+    }
+}
+
+impl<T: Any> DerefMut for CfgMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.line.set_dirty();
+        // Safety: the write flag is held.
+        unsafe { self.line.value_mut() }
+            .as_any_mut()
+            .downcast_mut::<T>()
+            .expect("downcast failed")
+    }
+}
+
+impl<T: Any> CfgMut<'_, T> {
+    /// The counters of `T`, for the writes bypassing the cache.
+    pub(crate) fn counters(&self) -> &Counters {
+        &self.line.counters
+    }
+
+    /// Replace the value which has already been written back, so it's not marked as dirty.
+    pub(crate) fn set_stored(&mut self, value: T) {
+        // Safety: the write flag is held.
+        *unsafe { self.line.value_mut() }
+            .as_any_mut()
+            .downcast_mut::<T>()
+            .expect("downcast failed") = value;
+        self.line.set_stored();
+    }
+}
+
+impl<T> Drop for CfgMut<'_, T> {
+    fn drop(&mut self) {
+        self.line.end_write();
+    }
+}
 
 /// This trait is used to retrieve the config value from the cache.
 #[allow(private_bounds, private_interfaces)]
@@ -112,26 +499,46 @@ pub trait Cacheable<T> {
     type Ref<'a>;
     /// Mutable reference retrieved from the cache.
     type Mut<'a>;
+    /// Try to retrieve the immutable ref from the cache.
+    fn try_retrieve<const N: usize>(cache: &Cache<N>) -> ConfigResult<Self::Ref<'_>>;
+    /// Try to retrieve the mutable ref from the cache.
+    fn try_retrieve_mut<const N: usize>(cache: &Cache<N>) -> ConfigResult<Self::Mut<'_>>;
     /// Retrieve the immutable ref from the cache.
-    fn retrieve<const N: usize>(cache: &rom_cache::Cache<1, N>) -> Self::Ref<'_>;
+    ///
+    /// # Panic
+    /// - If [`Cacheable::try_retrieve()`] failed.
+    fn retrieve<const N: usize>(cache: &Cache<N>) -> Self::Ref<'_> {
+        Self::try_retrieve(cache).unwrap_or_else(|e| panic!("{e}"))
+    }
     /// Retrieve the mutable ref from the cache.
-    fn retrieve_mut<const N: usize>(cache: &rom_cache::Cache<1, N>) -> Self::Mut<'_>;
+    ///
+    /// # Panic
+    /// - If [`Cacheable::try_retrieve_mut()`] failed.
+    fn retrieve_mut<const N: usize>(cache: &Cache<N>) -> Self::Mut<'_> {
+        Self::try_retrieve_mut(cache).unwrap_or_else(|e| panic!("{e}"))
+    }
 }
 
 #[allow(private_bounds, private_interfaces)]
 impl<T> Cacheable<()> for T
 where
-    T: rom_cache::Cacheable + Default,
+    T: crate::source::Cacheable + Default,
 {
     type Ref<'a> = CfgRef<'a, T>;
     type Mut<'a> = CfgMut<'a, T>;
 
-    fn retrieve<const N: usize>(cache: &rom_cache::Cache<1, N>) -> Self::Ref<'_> {
-        cache.get::<T>().unwrap()
+    fn try_retrieve<const N: usize>(cache: &Cache<N>) -> ConfigResult<Self::Ref<'_>> {
+        cache.get::<T>().map(|line| CfgRef {
+            line,
+            _phantom: PhantomData,
+        })
     }
 
-    fn retrieve_mut<const N: usize>(cache: &rom_cache::Cache<1, N>) -> Self::Mut<'_> {
-        cache.get_mut::<T>().unwrap()
+    fn try_retrieve_mut<const N: usize>(cache: &Cache<N>) -> ConfigResult<Self::Mut<'_>> {
+        cache.get_mut::<T>().map(|line| CfgMut {
+            line,
+            _phantom: PhantomData,
+        })
     }
 }
 
@@ -144,12 +551,12 @@ macro_rules! impl_cacheable {
         {
             type Ref<'a> = ($(<$t as Cacheable<()>>::Ref<'a>),+,);
             type Mut<'a> = ($(<$t as Cacheable<()>>::Mut<'a>),+,);
-            fn retrieve<const N: usize>(cache: &rom_cache::Cache<1, N>) -> Self::Ref<'_> {
-                ($(<$t as Cacheable<()>>::retrieve(cache)),+,)
+            fn try_retrieve<const N: usize>(cache: &Cache<N>) -> ConfigResult<Self::Ref<'_>> {
+                Ok(($(<$t as Cacheable<()>>::try_retrieve(cache)?),+,))
             }
 
-            fn retrieve_mut<const N: usize>(cache: &rom_cache::Cache<1, N>) -> Self::Mut<'_> {
-                ($(<$t as Cacheable<()>>::retrieve_mut(cache)),+,)
+            fn try_retrieve_mut<const N: usize>(cache: &Cache<N>) -> ConfigResult<Self::Mut<'_>> {
+                Ok(($(<$t as Cacheable<()>>::try_retrieve_mut(cache)?),+,))
             }
         }
     };
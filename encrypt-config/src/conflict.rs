@@ -0,0 +1,208 @@
+//! # Conflict
+//! Detect the files behind [`PersistSource`](crate::source::PersistSource) and [`SecretSource`](crate::source::SecretSource)
+//! modified by others (e.g. edited by hand) since loaded, before writing back.
+//!
+//! The modification time and the content hash of the file are recorded on loading and writing back,
+//! and the file is taken as modified if either changed. The hash catches the edits within the granularity of the modification time.
+//! What to do then is chosen by [`ConflictPolicy`] with `#[source(conflict = ...)]` on the derive macros,
+//! the changes are merged field by field by default.
+//!
+//! Writing back a value which was never loaded (e.g. inserted by [`Config::insert()`](crate::Config::insert)) is not checked.
+
+use crate::error::Conflict;
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::SystemTime,
+};
+
+/// What to do when the file was modified by others since loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Fail with [`ConfigError::Conflict`](crate::error::ConfigError::Conflict), until the value is reloaded.
+    Fail,
+    /// Overwrite the file with the cached value, the changes of the file are lost.
+    Overwrite,
+    /// Keep the file modified, the changes of the cached value are not written back until the value is reloaded.
+    KeepTheirs,
+    /// Merge field by field, taking the value loaded as the common base:
+    /// the fields changed by only one side are taken from that side.
+    /// Fail with [`ConfigError::Conflict`](crate::error::ConfigError::Conflict) naming the fields changed differently by both sides.
+    ///
+    /// The cached value is not updated with the fields taken from the file, reload it to see them.
+    /// They are kept on the later write-backs unless changed in the cache, in which case they conflict until reloaded.
+    #[default]
+    Merge,
+}
+
+/// How to write back, resolved by [`ConflictPolicy`].
+pub(crate) enum Resolved {
+    /// Write the cached value.
+    Ours,
+    /// Write the value merged.
+    Merged(Value),
+    /// Keep the file.
+    Theirs,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct FileState {
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+#[derive(Clone)]
+struct Record {
+    /// `None` if the file did not exist.
+    state: Option<FileState>,
+    /// The value the cache believes to be in the file, the base of merging.
+    base: Value,
+}
+
+/// Record the state of the file at `path` and the value `base` in it, after loading or writing back.
+pub(crate) fn record(path: &Path, base: Value) -> io::Result<()> {
+    let state = read_state(path)?.map(|(state, _)| state);
+    records().insert(path.to_owned(), Record { state, base });
+    Ok(())
+}
+
+/// The record of a file saved by [`save()`], to be put back by [`restore()`].
+#[derive(Clone)]
+pub(crate) struct Saved(Option<Record>);
+
+/// Save the record of the file at `path`, before writing it back in a transaction.
+pub(crate) fn save(path: &Path) -> Saved {
+    Saved(records().get(path).cloned())
+}
+
+/// Put back the record saved, after the file at `path` is restored on rolling back.
+pub(crate) fn restore(path: &Path, saved: Saved) {
+    let mut records = records();
+    match saved.0 {
+        Some(record) => records.insert(path.to_owned(), record),
+        None => records.remove(path),
+    };
+}
+
+/// Resolve how to write back `ours` as `policy`, `parse` reads the value in the file modified.
+/// - ConfigError::Conflict (carried by the io error): the file was modified and cannot be overwritten as `policy`.
+pub(crate) fn resolve(
+    path: &Path,
+    policy: ConflictPolicy,
+    ours: &Value,
+    parse: impl FnOnce(&[u8]) -> io::Result<Value>,
+) -> io::Result<Resolved> {
+    if policy == ConflictPolicy::Overwrite {
+        return Ok(Resolved::Ours);
+    }
+    let Some(record) = records().get(path).cloned() else {
+        return Ok(Resolved::Ours);
+    };
+    let (state, contents) = match read_state(path)? {
+        Some((state, contents)) => (Some(state), contents),
+        None => (None, vec![]),
+    };
+    let modified = record.state != state;
+    match policy {
+        ConflictPolicy::Fail if modified => Err(conflict(path, vec![])),
+        ConflictPolicy::KeepTheirs if modified => Ok(Resolved::Theirs),
+        // Always merge, since the file may hold the fields merged which are not in the cache.
+        ConflictPolicy::Merge => {
+            let theirs = match state {
+                Some(_) => Some(parse(&contents)?),
+                None => None,
+            };
+            // No base if the file did not exist, rather than `null`.
+            let base = record.state.as_ref().map(|_| &record.base);
+            let mut conflicts = vec![];
+            let merged = merge(base, Some(ours), theirs.as_ref(), "", &mut conflicts);
+            match (conflicts.is_empty(), merged) {
+                (false, _) => Err(conflict(path, conflicts)),
+                (true, Some(merged)) if merged != *ours => Ok(Resolved::Merged(merged)),
+                _ => Ok(Resolved::Ours),
+            }
+        }
+        _ => Ok(Resolved::Ours),
+    }
+}
+
+/// Three-way merge, the fields changed differently by both sides are pushed into `conflicts`, and taken from `ours`.
+fn merge(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    field: &str,
+    conflicts: &mut Vec<String>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+    if let (Some(Value::Object(o)), Some(Value::Object(t))) = (ours, theirs) {
+        let b = base.and_then(Value::as_object);
+        let keys = o
+            .keys()
+            .chain(t.keys())
+            .chain(b.into_iter().flat_map(Map::keys))
+            .collect::<BTreeSet<_>>();
+        let mut merged = Map::new();
+        for key in keys {
+            let field = match field {
+                "" => key.clone(),
+                field => format!("{field}.{key}"),
+            };
+            let base = b.and_then(|b| b.get(key));
+            if let Some(v) = merge(base, o.get(key), t.get(key), &field, conflicts) {
+                merged.insert(key.clone(), v);
+            }
+        }
+        return Some(Value::Object(merged));
+    }
+    conflicts.push(match field {
+        "" => "<root>".into(),
+        field => field.into(),
+    });
+    ours.cloned()
+}
+
+/// The state and the contents of the file, `None` if not exists.
+fn read_state(path: &Path) -> io::Result<Option<(FileState, Vec<u8>)>> {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let metadata = std::fs::metadata(path)?;
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    let state = FileState {
+        modified: metadata.modified().ok(),
+        hash: hasher.finish(),
+    };
+    Ok(Some((state, contents)))
+}
+
+fn conflict(path: &Path, fields: Vec<String>) -> io::Error {
+    io::Error::other(
+        Conflict {
+            path: path.to_owned(),
+            fields,
+        }
+        .build(),
+    )
+}
+
+fn records() -> MutexGuard<'static, HashMap<PathBuf, Record>> {
+    static RECORDS: OnceLock<Mutex<HashMap<PathBuf, Record>>> = OnceLock::new();
+    RECORDS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
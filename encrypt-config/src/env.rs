@@ -0,0 +1,98 @@
+//! # Env
+//! Overlay the environment variables on the loaded value, used by `#[source(env_prefix = "...")]` of the derive macros.
+//!
+//! The rest of the name after the prefix is lowercased as the field name, and `__` separates the nested fields,
+//! e.g. `APP_DATABASE__PORT=5433` overrides `database.port` with prefix `APP_`.
+
+use crate::layered::{parse, set, unset};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::io;
+
+/// Overlay the environment variables starting with `prefix` on `base`.
+///
+/// Each value is parsed as json the same as [`Layer::env()`](crate::Layer::env),
+/// and taken as a string if it's not json or the field does not accept it, so a string field needs no quotes.
+///
+/// The variables whose names are not valid unicode are skipped.
+///
+/// # Errors
+/// - [`io::ErrorKind::InvalidData`]: a value is not valid unicode or cannot be parsed into the field type,
+///   the variable is named in the message.
+pub fn overlay<T>(base: T, prefix: &str) -> io::Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut value = serde_json::to_value(base)?;
+    for (key, raw) in vars(prefix)? {
+        let path = key[prefix.len()..].to_lowercase();
+        set_raw::<T>(&mut value, path.split("__"), &raw).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid environment variable `{key}={raw}`: {e}"),
+            )
+        })?;
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Set the field at `path` of `value` to `raw`, which is parsed as json,
+/// or taken as a string if it's not json or the field of `T` does not accept it. `value` is left untouched on error.
+pub(crate) fn set_raw<'a, T: DeserializeOwned>(
+    value: &mut Value,
+    path: impl Iterator<Item = &'a str> + Clone,
+    raw: &str,
+) -> Result<(), serde_json::Error> {
+    let mut next = value.clone();
+    set(&mut next, path.clone(), parse(raw));
+    if let Err(json_err) = serde_json::from_value::<T>(next.clone()) {
+        next = value.clone();
+        set(&mut next, path, Value::String(raw.to_owned()));
+        // Report the error of json if the raw value looks like json, or the error of string otherwise.
+        serde_json::from_value::<T>(next.clone()).map_err(|e| {
+            match serde_json::from_str::<Value>(raw) {
+                Ok(_) => json_err,
+                Err(_) => e,
+            }
+        })?;
+    }
+    *value = next;
+    Ok(())
+}
+
+/// Restore the fields overlaid by the environment variables starting with `prefix` to the ones in `base`,
+/// so that the overlaid values are never written back. The fields absent in `base` are removed.
+pub fn without_overlay<T>(value: &T, base: &T, prefix: &str) -> io::Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let base = serde_json::to_value(base)?;
+    let mut value = serde_json::to_value(value)?;
+    for (key, _) in vars(prefix)? {
+        let path = key[prefix.len()..].to_lowercase();
+        let pointer = path.split("__").fold(String::new(), |p, k| p + "/" + k);
+        match base.pointer(&pointer) {
+            Some(v) => set(&mut value, path.split("__"), v.clone()),
+            None => unset(&mut value, path.split("__")),
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// The environment variables starting with `prefix`, sorted so that the result is deterministic.
+/// The names not valid unicode are skipped, and the values not valid unicode are errors.
+pub(crate) fn vars(prefix: &str) -> io::Result<Vec<(String, String)>> {
+    let mut vars = std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value)))
+        .filter(|(key, _)| key.len() > prefix.len() && key.starts_with(prefix))
+        .map(|(key, value)| match value.into_string() {
+            Ok(value) => Ok((key, value)),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("environment variable `{key}` is not valid unicode"),
+            )),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    vars.sort();
+    Ok(vars)
+}
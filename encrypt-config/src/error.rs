@@ -1,5 +1,6 @@
 //! The error types of `encrypt config`.
 
+use crate::validate::FieldError;
 use snafu::Snafu;
 
 /// The Error types of `encrypt config`, which is implemented by [`snafu`].
@@ -37,6 +38,106 @@ pub enum ConfigError {
         /// The error returned by `rsa`.
         source: rsa::Error,
     },
+    /// This error will be returned when the config value is already borrowed, e.g. getting a [`CfgMut`](crate::config::CfgMut) while a [`CfgRef`](crate::config::CfgRef) is alive.
+    #[snafu(display("Config `{type_name}` is already borrowed."))]
+    AlreadyBorrowed {
+        /// The type name of the config.
+        type_name: &'static str,
+    },
+    /// This error will be returned when the cache line chosen to evict is being used.
+    /// Consider dropping the refs you held or increasing the capacity of [`Config`](crate::Config).
+    #[snafu(display(
+        "Cannot cache config `{type_name}`, the cache line chosen to evict is being used."
+    ))]
+    CacheBusy {
+        /// The type name of the config.
+        type_name: &'static str,
+    },
+    /// This error will be returned when the config value cannot be loaded into the cache.
+    #[snafu(display("Failed to load config `{type_name}` into the cache: {source}"))]
+    LoadFailed {
+        /// The type name of the config.
+        type_name: &'static str,
+        /// The underlying io error.
+        source: std::io::Error,
+    },
+    /// This error will be returned when the config value cannot be written back.
+    #[snafu(display("Failed to write back config `{type_name}`: {source}"))]
+    StoreFailed {
+        /// The type name of the config.
+        type_name: &'static str,
+        /// The underlying io error.
+        source: std::io::Error,
+    },
+    /// This error will be returned when some config values cannot be written back during flushing.
+    #[snafu(display("Failed to flush config:\n{}", display_errors(errors)))]
+    FlushFailed {
+        /// The errors of each config type failed.
+        errors: Vec<ConfigError>,
+    },
+    /// This error will be returned when committing a transaction failed, the sources written are rolled back.
+    #[snafu(display(
+        "Failed to commit the transaction: {source}{}",
+        display_rollback_errors(rollback_errors)
+    ))]
+    CommitFailed {
+        /// The error causing the transaction failed.
+        source: Box<ConfigError>,
+        /// The errors occurred during rolling back, the sources listed may be inconsistent.
+        rollback_errors: Vec<ConfigError>,
+    },
+    /// This error will be returned when the config value is invalid, checked on loading and before writing back.
+    /// See [`validate`](crate::validate) for more details.
+    #[snafu(display("Config `{type_name}` is invalid:\n{}", display_field_errors(errors)))]
+    ValidationFailed {
+        /// The type name of the config.
+        type_name: &'static str,
+        /// The errors of each invalid field.
+        errors: Vec<FieldError>,
+    },
+    #[cfg(feature = "persist")]
+    /// This error will be returned when the file of the config is locked by another process.
+    /// See [`lock`](crate::lock) for more details.
+    #[snafu(display("Config file `{}` is locked by another process.", path.display()))]
+    Locked {
+        /// The path of the config file.
+        path: std::path::PathBuf,
+    },
+    #[cfg(feature = "persist")]
+    /// This error will be returned when the file of the config was modified by others since loaded.
+    /// See [`conflict`](crate::conflict) for more details.
+    #[snafu(display(
+        "Config file `{}` was modified by others since loaded.{}",
+        path.display(),
+        display_conflicts(fields)
+    ))]
+    Conflict {
+        /// The path of the config file.
+        path: std::path::PathBuf,
+        /// The fields changed differently by both sides when merging, empty if not merging.
+        fields: Vec<String>,
+    },
+    #[cfg(feature = "watch")]
+    /// This error will be returned when watching a source which is not backed by a file.
+    #[snafu(display("Config `{type_name}` is not backed by a file, so it cannot be watched."))]
+    Unwatchable {
+        /// The type name of the config.
+        type_name: &'static str,
+    },
+    #[cfg(feature = "watch")]
+    /// This error will be returned when the file watcher cannot be set up.
+    #[snafu(display("Failed to watch the file: {source}"))]
+    WatchFailed {
+        /// The error returned by `notify`.
+        source: notify::Error,
+    },
+    #[cfg(feature = "async")]
+    /// This error will be returned when the blocking task loading or storing the config was cancelled.
+    #[snafu(display("The blocking task was cancelled: {source}"))]
+    TaskCancelled {
+        /// The error returned by `tokio`.
+        source: tokio::task::JoinError,
+    },
     /// This error will be returned when the config cannot be saved to or read from the file.
     #[snafu(display("IO error. Cannot operate the file."), context(false))]
     IoError {
@@ -45,5 +146,51 @@ pub enum ConfigError {
     },
 }
 
+fn display_errors(errors: &[ConfigError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("- {e}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn display_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("- {e}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(feature = "persist")]
+fn display_conflicts(fields: &[String]) -> String {
+    match fields.is_empty() {
+        true => String::new(),
+        false => format!("\nConflicting fields: {}", fields.join(", ")),
+    }
+}
+
+fn display_rollback_errors(errors: &[ConfigError]) -> String {
+    match errors.is_empty() {
+        true => String::new(),
+        false => format!("\nFailed to roll back:\n{}", display_errors(errors)),
+    }
+}
+
+/// Take out the [`ConfigError`] carried by the io error of a source (e.g. [`ConfigError::ValidationFailed`]),
+/// or wrap the io error by `wrap`.
+pub(crate) fn from_source_error(
+    e: std::io::Error,
+    wrap: impl FnOnce(std::io::Error) -> ConfigError,
+) -> ConfigError {
+    match e.get_ref().is_some_and(|inner| inner.is::<ConfigError>()) {
+        true => *e
+            .into_inner()
+            .and_then(|inner| inner.downcast().ok())
+            .expect("checked above"),
+        false => wrap(e),
+    }
+}
+
 /// The Result type of `encrypt config`, which is implemented by [`snafu`].
 pub type ConfigResult<T> = Result<T, ConfigError>;
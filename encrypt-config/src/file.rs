@@ -0,0 +1,58 @@
+//! Crash-safe file operations for the persisted sources.
+
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Replace the file at `path` with `contents` atomically, so that a crash or a full disk never leaves a truncated file.
+///
+/// The contents are written to a temporary file in the same directory and synced, then renamed over `path`,
+/// and the directory is synced so that the rename is durable. The permissions of the file replaced are kept.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let tmp = with_suffix(
+        path,
+        format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+    let res = (|| {
+        let mut file = File::create(&tmp)?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp, path)?;
+        sync_dir(path)
+    })();
+    if res.is_err() {
+        std::fs::remove_file(&tmp).ok();
+    }
+    res
+}
+
+/// Sync the directory containing `path`, only supported on unix.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// `path` with `suffix` appended to the file name, e.g. `config.json.bak.1`.
+pub(crate) fn with_suffix(path: &Path, suffix: String) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    path.into()
+}
@@ -0,0 +1,43 @@
+//! # Inspect
+//! Introspection of the config types held by [`Config`], without knowing the concrete types.
+
+use crate::{config::Config, source::SourceKind};
+use std::path::PathBuf;
+
+/// The metadata of a cached config type, see [`Config::entries()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CfgEntry {
+    /// The type name of the config.
+    pub type_name: &'static str,
+    /// The kind of the source.
+    pub kind: SourceKind,
+    /// The path of the file behind the source, `None` if not persisted.
+    pub path: Option<PathBuf>,
+    /// The keyring entry holding the key, `None` if not encrypted.
+    pub keyring_entry: Option<&'static str>,
+    /// Whether the value was changed but not written back yet.
+    pub dirty: bool,
+    /// The current borrow state.
+    pub borrow: BorrowState,
+}
+
+/// The borrow state of a cached config type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorrowState {
+    /// No ref is alive.
+    Unused,
+    /// Some [`CfgRef`](crate::config::CfgRef)s are alive.
+    Reading(usize),
+    /// A [`CfgMut`](crate::config::CfgMut) is alive.
+    Writing,
+}
+
+impl<const N: usize> Config<N> {
+    /// The metadata of each config type currently cached, the most recently used one comes first.
+    ///
+    /// This is a point-in-time view, e.g. for a debug page. The borrow states may change right after returned.
+    pub fn entries(&self) -> Vec<CfgEntry> {
+        self.cache.entries()
+    }
+}
@@ -0,0 +1,223 @@
+//! # Layered
+//! The layers of a [`LayeredSource`](crate::source::LayeredSource), merged field by field.
+
+use serde_json::{Map, Value};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A layer providing some fields of a [`LayeredSource`](crate::source::LayeredSource).
+///
+/// Layers are merged in order, the later ones override the fields provided by the earlier ones.
+/// Nested objects are merged recursively, other values are replaced as a whole.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    kind: LayerKind,
+}
+
+#[derive(Debug, Clone)]
+enum LayerKind {
+    Value(Value),
+    File { path: PathBuf, writable: bool },
+    Env(String),
+    Args(Vec<String>),
+}
+
+impl Layer {
+    /// A layer providing all fields of `value`, usually the defaults.
+    ///
+    /// # Panic
+    /// - If `value` cannot be serialized to json.
+    pub fn value<T: serde::Serialize>(value: &T) -> Self {
+        let value = serde_json::to_value(value).expect("failed to serialize the layer");
+        Self {
+            kind: LayerKind::Value(value),
+        }
+    }
+
+    /// A read-only json file layer, skipped if the file does not exist.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind: LayerKind::File {
+                path: path.into(),
+                writable: false,
+            },
+        }
+    }
+
+    /// A json file layer which the changes are written back to, skipped if the file does not exist.
+    /// If more than one writable layer is given, the last one is used.
+    pub fn writable_file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind: LayerKind::File {
+                path: path.into(),
+                writable: true,
+            },
+        }
+    }
+
+    /// A layer of the environment variables starting with `prefix`.
+    ///
+    /// The rest of the name is lowercased as the field name, and `__` separates the nested fields,
+    /// e.g. `APP_DB__PORT=5432` provides `{"db": {"port": 5432}}` with prefix `APP_`.
+    /// The value is parsed as json, or taken as a string if failed. So quote it if a string field looks like a number,
+    /// e.g. `APP_NAME='"42"'`.
+    ///
+    /// The variables whose names are not valid unicode are skipped, and the values not valid unicode fail the loading.
+    pub fn env(prefix: impl Into<String>) -> Self {
+        Self {
+            kind: LayerKind::Env(prefix.into()),
+        }
+    }
+
+    /// A layer of the command line arguments, e.g. `std::env::args().skip(1)`.
+    ///
+    /// `--key=value` and `--key value` are accepted, `.` separates the nested fields and `-` in keys is taken as `_`.
+    /// A `--flag` without value provides `true`. The value is parsed as json, or taken as a string if failed.
+    /// Other arguments are ignored.
+    pub fn args<I>(args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            kind: LayerKind::Args(args.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// The path of the writable file layer.
+    pub(crate) fn writable_path(&self) -> Option<&Path> {
+        match &self.kind {
+            LayerKind::File {
+                path,
+                writable: true,
+            } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The fields provided, `None` if nothing is provided.
+    pub(crate) fn provide(&self) -> io::Result<Option<Value>> {
+        match &self.kind {
+            LayerKind::Value(value) => Ok(Some(value.clone())),
+            LayerKind::File { path, .. } => read_file(path),
+            LayerKind::Env(prefix) => {
+                let mut value = Value::Object(Map::new());
+                for (key, v) in crate::env::vars(prefix)? {
+                    let key = key[prefix.len()..].to_lowercase();
+                    set(&mut value, key.split("__"), parse(&v));
+                }
+                Ok(Some(value))
+            }
+            LayerKind::Args(args) => {
+                let mut value = Value::Object(Map::new());
+                let mut args = args.iter().peekable();
+                while let Some(arg) = args.next() {
+                    let Some(arg) = arg.strip_prefix("--") else {
+                        continue;
+                    };
+                    let (key, v) = match arg.split_once('=') {
+                        Some((key, v)) => (key, parse(v)),
+                        None => match args.next_if(|next| !next.starts_with("--")) {
+                            Some(v) => (arg, parse(v)),
+                            None => (arg, Value::Bool(true)),
+                        },
+                    };
+                    let key = key.replace('-', "_");
+                    set(&mut value, key.split('.'), v);
+                }
+                Ok(Some(value))
+            }
+        }
+    }
+}
+
+/// Merge all the layers in order.
+pub(crate) fn merge_layers(layers: &[Layer]) -> io::Result<Value> {
+    let mut merged = Value::Object(Map::new());
+    for layer in layers {
+        if let Some(value) = layer.provide()? {
+            merge(&mut merged, value);
+        }
+    }
+    Ok(merged)
+}
+
+/// Merge `value` into `base`, nested objects are merged recursively.
+pub(crate) fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Object(base), Value::Object(value)) => {
+            for (key, v) in value {
+                match base.get_mut(&key) {
+                    Some(b) => merge(b, v),
+                    None => {
+                        base.insert(key, v);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// The fields of `new` different from `old`, `None` if no difference.
+pub(crate) fn diff(old: &Value, new: &Value) -> Option<Value> {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let changes = new
+                .iter()
+                .filter_map(|(key, v)| match old.get(key) {
+                    Some(o) => diff(o, v).map(|d| (key.clone(), d)),
+                    None => Some((key.clone(), v.clone())),
+                })
+                .collect::<Map<_, _>>();
+            (!changes.is_empty()).then_some(Value::Object(changes))
+        }
+        (old, new) => (old != new).then(|| new.clone()),
+    }
+}
+
+pub(crate) fn read_file(path: &Path) -> io::Result<Option<Value>> {
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(Some(serde_json::from_reader(io::BufReader::new(file))?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn set<'a>(value: &mut Value, mut keys: impl Iterator<Item = &'a str>, v: Value) {
+    let Some(key) = keys.next() else {
+        *value = v;
+        return;
+    };
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    if let Value::Object(map) = value {
+        set(map.entry(key).or_insert(Value::Null), keys, v);
+    }
+}
+
+/// Remove the field at `keys` of `value`, nothing is done if it's absent.
+pub(crate) fn unset<'a>(value: &mut Value, keys: impl Iterator<Item = &'a str>) {
+    let mut keys = keys.peekable();
+    let mut value = value;
+    while let Some(key) = keys.next() {
+        let Value::Object(map) = value else {
+            return;
+        };
+        if keys.peek().is_none() {
+            map.remove(key);
+            return;
+        }
+        let Some(v) = map.get_mut(key) else {
+            return;
+        };
+        value = v;
+    }
+}
+
+pub(crate) fn parse(v: &str) -> Value {
+    serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.to_owned()))
+}
@@ -14,13 +14,55 @@ compile_error!("Feature `mock` is designed only for feature `secret` on.");
 /// The output directory for the generated files when testing.
 pub const TEST_OUT_DIR: &str = concat!(env!("OUT_DIR"), "/encrypt_config_cache");
 
+#[cfg(feature = "async")]
+pub mod async_config;
+#[cfg(feature = "persist")]
+pub mod backup;
+mod cache;
+#[cfg(feature = "clap")]
+pub mod cli;
 pub mod config;
+#[cfg(feature = "persist")]
+pub mod conflict;
 #[cfg(feature = "secret")]
 pub mod encrypt_utils;
+#[cfg(feature = "persist")]
+pub mod env;
 pub mod error;
+#[cfg(feature = "persist")]
+mod file;
+pub mod inspect;
+#[cfg(feature = "persist")]
+pub mod layered;
+#[cfg(feature = "persist")]
+pub mod lock;
+#[cfg(feature = "persist")]
+pub mod migrate;
 pub mod source;
+pub mod stats;
+pub mod subscribe;
+pub mod transaction;
+pub mod validate;
+#[cfg(feature = "watch")]
+pub mod watch;
 
-pub use config::Config;
+#[cfg(feature = "async")]
+pub use async_config::AsyncConfig;
+#[cfg(feature = "clap")]
+pub use cli::CliSource;
+pub use config::{global, Config, ConfigBuilder, DynConfig};
+#[cfg(feature = "persist")]
+pub use conflict::ConflictPolicy;
 #[cfg(feature = "derive")]
 pub use encrypt_config_derive::*;
+pub use inspect::{BorrowState, CfgEntry};
+#[cfg(feature = "persist")]
+pub use layered::Layer;
+#[cfg(feature = "persist")]
+pub use lock::LockMode;
 pub use source::*;
+pub use stats::CfgStats;
+pub use subscribe::{CfgEvicted, CfgSubscription};
+pub use validate::{FieldError, Validate};
+#[cfg(feature = "watch")]
+pub use watch::CfgWatcher;
@@ -0,0 +1,105 @@
+//! # Lock
+//! Cross-process advisory locks on the files behind [`PersistSource`](crate::source::PersistSource)
+//! and [`SecretSource`](crate::source::SecretSource), so that the processes sharing a file do not clobber each other.
+//!
+//! The locks are taken on the sidecar files next to the config file, since the file itself is replaced on writing back:
+//! - `<file>.lock`: held exclusively during each loading and writing back, waiting for the other processes to finish.
+//! - `<file>.owner`: held exclusively by the owner for the whole run with [`LockMode::Exclusive`].
+//!   The other processes can still load the file, but fail to write back with
+//!   [`ConfigError::Locked`](crate::error::ConfigError::Locked).
+//!
+//! If the `.lock` file cannot be created on loading (e.g. in a read-only directory), the file is loaded without the lock.
+//! The sidecar files are left in place, removing them would race with the other processes.
+//! The locks are advisory, the processes not using this crate are not stopped.
+
+use crate::{error::Locked, file::with_suffix};
+use fs4::{FileExt, TryLockError};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+/// How the file of a source is locked against the other processes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LockMode {
+    /// Lock the file during each loading and writing back, any process can write back.
+    #[default]
+    Shared,
+    /// Besides locking as [`LockMode::Shared`], own the file for the whole run on the first loading or writing back,
+    /// so that this process is the only one writing back.
+    /// Fails with [`ConfigError::Locked`](crate::error::ConfigError::Locked) if another process owns it or is writing back.
+    Exclusive,
+    /// No lock at all.
+    None,
+}
+
+/// The locks held, released on dropping.
+pub(crate) struct LockGuard {
+    _files: Vec<File>,
+}
+
+/// Lock `path` for loading (`write == false`) or writing back (`write == true`) as `mode`.
+pub(crate) fn lock(path: &Path, mode: LockMode, write: bool) -> io::Result<LockGuard> {
+    let mut files = vec![];
+    match mode {
+        LockMode::None => return Ok(LockGuard { _files: files }),
+        LockMode::Exclusive => own(path)?,
+        // Check no other process owns the file, and keep it from being owned during writing back.
+        LockMode::Shared if write => {
+            let owner = open(&with_suffix(path, ".owner".into()))?;
+            try_lock(path, FileExt::try_lock_shared(&owner))?;
+            files.push(owner);
+        }
+        LockMode::Shared => {}
+    }
+    let file = match open(&with_suffix(path, ".lock".into())) {
+        Ok(file) => file,
+        Err(_) if !write => return Ok(LockGuard { _files: files }),
+        Err(e) => return Err(e),
+    };
+    FileExt::lock(&file)?;
+    files.push(file);
+    Ok(LockGuard { _files: files })
+}
+
+/// Own `path` for the whole run if not owned yet.
+fn own(path: &Path) -> io::Result<()> {
+    static OWNED: OnceLock<Mutex<HashMap<PathBuf, File>>> = OnceLock::new();
+    let mut owned = OWNED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if owned.contains_key(path) {
+        return Ok(());
+    }
+    let owner = open(&with_suffix(path, ".owner".into()))?;
+    try_lock(path, FileExt::try_lock(&owner))?;
+    owned.insert(path.to_owned(), owner);
+    Ok(())
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+fn try_lock(path: &Path, res: Result<(), TryLockError>) -> io::Result<()> {
+    match res {
+        Ok(()) => Ok(()),
+        Err(TryLockError::WouldBlock) => Err(io::Error::other(
+            Locked {
+                path: path.to_owned(),
+            }
+            .build(),
+        )),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
@@ -0,0 +1,113 @@
+//! # Migrate
+//! Versioned schemas of the [`PersistSource`](crate::source::PersistSource).
+//!
+//! The version is stored in the file under the key [`VERSION_KEY`] of the top-level object.
+//! A file without it is taken as version `0`. On loading, the migrations from the file's version
+//! to the current one are run in order on the raw json, and the file is rewritten in the new format.
+//!
+//! # Example
+//! ```no_run
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use encrypt_config::{Config, PersistSource};
+//! # use serde::{Deserialize, Serialize};
+//! use serde_json::{json, Value};
+//!
+//! #[derive(Default, Serialize, Deserialize, PersistSource)]
+//! #[cfg_attr(
+//!     feature = "default_config_dir",
+//!     source(name = "app.json", version = 2, migrations = [v0_to_v1, v1_to_v2])
+//! )]
+//! #[cfg_attr(
+//!     not(feature = "default_config_dir"),
+//!     source(path = "/path/to/app.json", version = 2, migrations = [v0_to_v1, v1_to_v2])
+//! )]
+//! struct AppConfig {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! /// `addr` was renamed to `host`.
+//! fn v0_to_v1(mut value: Value) -> Value {
+//!     if let Some(addr) = value.as_object_mut().and_then(|m| m.remove("addr")) {
+//!         value["host"] = addr;
+//!     }
+//!     value
+//! }
+//!
+//! /// `port` was added.
+//! fn v1_to_v2(mut value: Value) -> Value {
+//!     value["port"] = json!(8080);
+//!     value
+//! }
+//!
+//! let cfg: Config<1> = Config::new();
+//! assert_eq!(cfg.get::<AppConfig>().port, 8080);
+//! # }
+//! ```
+
+use serde_json::Value;
+use std::io;
+
+/// The key of the version in the top-level object of the file.
+pub const VERSION_KEY: &str = "$version";
+
+/// A step upgrading the raw json of a config by one version.
+pub type Migration = fn(Value) -> Value;
+
+/// Run the migrations from the version in `value` to `version`, the `i`th of `migrations` upgrading version `i` to `i + 1`.
+/// Returns the upgraded value without the version key, and whether any migration was run.
+///
+/// # Errors
+/// - [`io::ErrorKind::InvalidData`]: `value` is not an object, or the version in it is invalid.
+/// - [`io::ErrorKind::Unsupported`]: the version in `value` is newer than `version`, or a migration needed is missing.
+pub(crate) fn migrate(
+    value: Value,
+    version: u32,
+    migrations: &[Migration],
+) -> io::Result<(Value, bool)> {
+    let Value::Object(mut map) = value else {
+        return Err(invalid("a versioned config must be a json object".into()));
+    };
+    let from = match map.remove(VERSION_KEY) {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| invalid(format!("invalid `{VERSION_KEY}`: {v}")))?,
+    };
+    if from > version {
+        return Err(unsupported(format!(
+            "config version {from} is newer than the supported {version}"
+        )));
+    }
+    let value = Value::Object(map);
+    if from == version {
+        return Ok((value, false));
+    }
+    let steps = migrations
+        .get(from as usize..version as usize)
+        .ok_or_else(|| {
+            unsupported(format!(
+                "no migration to upgrade from version {}",
+                migrations.len().max(from as usize)
+            ))
+        })?;
+    Ok((steps.iter().fold(value, |value, step| step(value)), true))
+}
+
+/// Put `version` into `value` if it is an object and `version` is not `0`.
+pub(crate) fn with_version(mut value: Value, version: u32) -> Value {
+    if let (Value::Object(map), 1..) = (&mut value, version) {
+        map.insert(VERSION_KEY.into(), version.into());
+    }
+    value
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn unsupported(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg)
+}
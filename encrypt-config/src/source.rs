@@ -1,26 +1,171 @@
 //! Source module for the encrypt-config crate.
 
+#[cfg(feature = "persist")]
+use crate::backup::{read_or_recover, rotate};
+#[cfg(feature = "persist")]
+use crate::conflict::{record, resolve, ConflictPolicy, Resolved};
 #[cfg(feature = "secret")]
 use crate::encrypt_utils::Encrypter;
 #[cfg(feature = "persist")]
+use crate::file::write_atomic;
+#[cfg(feature = "persist")]
+use crate::layered::{diff, merge, merge_layers, read_file, Layer};
+#[cfg(feature = "persist")]
+use crate::lock::{lock, LockMode};
+#[cfg(feature = "persist")]
+use crate::migrate::{migrate, with_version, Migration};
+#[cfg(feature = "persist")]
 use serde::{de::DeserializeOwned, Serialize};
 #[cfg(feature = "persist")]
-use std::path::PathBuf;
+use std::path::Path;
+use std::{any::Any, path::PathBuf};
+
+/// A type that can be cached by [`Config`](crate::Config).
+pub trait Cacheable: Any + Send + Sync {
+    /// Load Cacheable from the storage
+    fn load() -> std::io::Result<Self>
+    where
+        Self: Sized;
+    /// Write Cacheable back to storage.
+    fn store(&self) -> std::io::Result<()>;
+    /// Load Cacheable again since the storage changed, e.g. on `Config::watch()` (feature `watch`).
+    /// Same as [`Cacheable::load()`] by default.
+    /// The derive macros of `PersistSource` and `SecretSource` never repair the file here, since it may be half-written.
+    fn refresh() -> std::io::Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::load()
+    }
+    /// Path of the file behind the source, `None` if not persisted.
+    /// This is implemented by the derive macros of `PersistSource` and `SecretSource`.
+    fn source_path() -> Option<PathBuf>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    /// Kind of the source, [`SourceKind::Custom`] if implemented by hand.
+    /// This is implemented by the derive macros.
+    fn source_kind() -> SourceKind
+    where
+        Self: Sized,
+    {
+        SourceKind::Custom
+    }
+    /// What to do when the source fails to load on cache miss, see [`Fallback`].
+    /// This is implemented by the derive macros of `PersistSource` and `SecretSource` with `#[source(fallback = ...)]`.
+    fn fallback() -> Fallback
+    where
+        Self: Sized,
+    {
+        Fallback::default()
+    }
+    /// Keyring entry holding the key of the source, `None` if not encrypted.
+    /// This is implemented by the derive macro of `SecretSource`.
+    fn keyring_entry() -> Option<&'static str>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// As Any. This is needed since `Cacheable` will be used as `&dyn Cacheable`,
+    /// and cannot upcast to `&dyn Any` in stable Rust. Just coding as following is Ok.
+    /// ```ignore
+    /// fn as_any(&self) -> &dyn Any {
+    ///     self
+    /// }
+    /// ```
+    fn as_any(&self) -> &dyn Any;
+    /// As Any mut.
+    /// ```ignore
+    /// fn as_any_mut(&mut self) -> &mut dyn Any {
+    ///     self
+    /// }
+    /// ```
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
 
-pub use rom_cache::Cacheable;
+/// The kind of a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SourceKind {
+    /// Derived by `NormalSource`, which is not persisted.
+    Normal,
+    /// Derived by `PersistSource`.
+    Persist,
+    /// Derived by `SecretSource`.
+    Secret,
+    /// Derived by `LayeredSource`.
+    Layered,
+    /// [`Cacheable`] implemented by hand.
+    Custom,
+}
+
+/// What to do when a source fails to load on cache miss.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fallback {
+    /// Fall back to the default value only if the source does not exist, e.g. the file is not found.
+    /// Other failures, e.g. a corrupt file, a wrong key or a permission error, are returned as
+    /// [`ConfigError::LoadFailed`](crate::error::ConfigError::LoadFailed), and the source is left untouched.
+    #[default]
+    NotFound,
+    /// Fall back to the default value on any failure.
+    /// Caution: the source failed is overwritten by the next write-back.
+    Always,
+    /// Never fall back, the source not existing is also an error.
+    Never,
+}
+
+impl Fallback {
+    /// Whether to fall back to the default value when loading failed with `e`.
+    pub fn allows(self, e: &std::io::Error) -> bool {
+        match self {
+            Fallback::NotFound => e.kind() == std::io::ErrorKind::NotFound,
+            Fallback::Always => true,
+            Fallback::Never => false,
+        }
+    }
+}
 
 /// Normal source trait.
-pub trait NormalSource: rom_cache::Cacheable {}
+pub trait NormalSource: Cacheable {}
 
 /// Persist source trait.
+///
+/// # Attributes
+/// The derive macro takes these in `#[source(...)]`, all but the path are optional:
+/// - `path = "..."`, or `name = "..."` under the config dir with feature `default_config_dir`: the file.
+/// - `env_prefix = "APP_"`: the environment variables like `APP_DATABASE__PORT` override the values loaded,
+///   and are never written back, which needs the type to be `Default`, see [`env`](crate::env).
+/// - `cli` (feature `clap`): the overrides recorded by `CliSource` apply the same, see the `cli` module.
+/// - `validate = path::to::fn`: validate on loading and before writing back, the `Validate` derived is used
+///   if some fields have `#[config(...)]`, see [`validate`](crate::validate).
+/// - `fallback = Fallback::Always`: see [`Cacheable::fallback()`] and [`Fallback`].
+/// - `backups = 3`: see [`PersistSource::BACKUPS`].
+/// - `lock = LockMode::Exclusive`: see [`PersistSource::LOCK`].
+/// - `conflict = ConflictPolicy::Fail`: see [`PersistSource::CONFLICT`].
+/// - `version = 2, migrations = [v0_to_v1, v1_to_v2]`: see [`PersistSource::VERSION`] and [`PersistSource::MIGRATIONS`].
 #[cfg(feature = "persist")]
-pub trait PersistSource: rom_cache::Cacheable + Serialize + DeserializeOwned {
+pub trait PersistSource: Cacheable + Serialize + DeserializeOwned {
     /// Path for the persist source.
     #[cfg(not(feature = "default_config_dir"))]
     const PATH: &'static str;
     /// Name for the persist source.
     #[cfg(feature = "default_config_dir")]
     const NAME: &'static str;
+    /// Version of the schema, stored in the file if not `0`, see [`migrate`](crate::migrate).
+    const VERSION: u32 = 0;
+    /// Migrations upgrading the older files, the `i`th one upgrading version `i` to `i + 1`.
+    const MIGRATIONS: &'static [Migration] = &[];
+    /// Number of the backups kept before writing back, see [`backup`](crate::backup).
+    const BACKUPS: usize = 0;
+    /// How the file is locked against the other processes, see [`lock`](crate::lock).
+    const LOCK: LockMode = LockMode::Shared;
+    /// What to do if the file was modified by others since loaded, see [`conflict`](crate::conflict).
+    const CONFLICT: ConflictPolicy = ConflictPolicy::Merge;
 
     /// Path for the persist source.
     fn path() -> PathBuf {
@@ -35,26 +180,111 @@ pub trait PersistSource: rom_cache::Cacheable + Serialize + DeserializeOwned {
                 .join(Self::NAME)
         }
     }
-    /// Load the persist source.
+    /// Load the persist source, with the file locked.
+    /// If versioned, the older file is upgraded by the migrations and rewritten in the new format.
+    /// If corrupt and backups are kept, the newest valid backup is restored, see [`backup`](crate::backup).
     fn load() -> std::io::Result<Self> {
         let path = Self::path();
-        let file = std::fs::File::open(path)?;
-        Ok(serde_json::from_reader(file)?)
+        let guard = lock(&path, Self::LOCK, false)?;
+        let res = read_or_recover(&path, Self::BACKUPS, true, parse_persist::<Self>);
+        record_loaded(&path, res.as_ref().map(|(this, _)| this))?;
+        let (this, migrated) = res?;
+        drop(guard);
+        if migrated {
+            PersistSource::store(&this)?;
+        }
+        Ok(this)
+    }
+    /// Read the persist source without recording it for detecting the conflicts, nor rewriting the file migrated.
+    /// This is used to get the values in the file before writing back.
+    fn read() -> std::io::Result<Self> {
+        let path = Self::path();
+        let _lock = lock(&path, Self::LOCK, false)?;
+        Ok(read_or_recover(&path, Self::BACKUPS, true, parse_persist::<Self>)?.0)
     }
-    /// Save the persist source.
+    /// Load the persist source again since the file changed, e.g. being edited.
+    /// Unlike [`PersistSource::load()`], a corrupt file is returned as an error instead of recovered from the backups,
+    /// since it may be half-written, and the file migrated is not rewritten.
+    fn refresh() -> std::io::Result<Self> {
+        let path = Self::path();
+        let _lock = lock(&path, Self::LOCK, false)?;
+        let res = read_or_recover(&path, 0, false, parse_persist::<Self>);
+        record_loaded(&path, res.as_ref().map(|(this, _)| this))?;
+        Ok(res?.0)
+    }
+    /// Save the persist source with the version if not `0`, the file is locked and replaced atomically.
+    /// If the file was modified by others since loaded, it's resolved as [`PersistSource::CONFLICT`].
     fn store(&self) -> std::io::Result<()> {
         let path = Self::path();
         let parent = path.parent().unwrap();
         std::fs::create_dir_all(parent)?;
-        let file = std::fs::File::create(path)?;
-        serde_json::to_writer(file, self)?;
-        Ok(())
+        let _lock = lock(&path, Self::LOCK, true)?;
+        let ours = serde_json::to_value(self)?;
+        let contents = match resolve(&path, Self::CONFLICT, &ours, |bytes| {
+            Ok(serde_json::to_value(parse_persist::<Self>(bytes)?.0)?)
+        })? {
+            Resolved::Theirs => return Ok(()),
+            Resolved::Ours if Self::VERSION == 0 => serde_json::to_vec(self)?,
+            Resolved::Ours => serde_json::to_vec(&with_version(ours.clone(), Self::VERSION))?,
+            Resolved::Merged(merged) => serde_json::to_vec(&with_version(merged, Self::VERSION))?,
+        };
+        rotate(&path, Self::BACKUPS)?;
+        write_atomic(&path, &contents)?;
+        // The base is the cached value rather than the merged one, so that the fields merged from the file
+        // are kept on the next write-back, since the cache does not hold them.
+        record(&path, ours)
+    }
+}
+
+/// Layered source trait, built from an ordered stack of [`Layer`]s merged field by field,
+/// e.g. defaults, system file, user file, environment variables and command line arguments.
+#[cfg(feature = "persist")]
+pub trait LayeredSource: Cacheable + Serialize + DeserializeOwned {
+    /// The layers from the lowest priority to the highest.
+    fn layers() -> Vec<Layer>;
+
+    /// Path of the writable layer, `None` if all layers are read-only.
+    fn path() -> Option<PathBuf> {
+        Self::layers()
+            .iter()
+            .rev()
+            .find_map(|l| l.writable_path().map(Into::into))
+    }
+    /// Load the layered source, merging all the layers.
+    fn load() -> std::io::Result<Self> {
+        let merged = merge_layers(&Self::layers())?;
+        Ok(serde_json::from_value(merged)?)
+    }
+    /// Save the layered source. Only the fields changed are written to the writable layer,
+    /// so the values from the other layers (e.g. environment variables) are not baked into the file.
+    fn store(&self) -> std::io::Result<()> {
+        let layers = Self::layers();
+        let loaded = merge_layers(&layers)?;
+        let Some(changes) = diff(&loaded, &serde_json::to_value(self)?) else {
+            return Ok(());
+        };
+        let path = layers
+            .iter()
+            .rev()
+            .find_map(|l| l.writable_path())
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::Unsupported, "no writable layer")
+            })?;
+        let mut written = read_file(path)?.unwrap_or_default();
+        merge(&mut written, changes);
+        let parent = path.parent().unwrap();
+        std::fs::create_dir_all(parent)?;
+        write_atomic(path, &serde_json::to_vec(&written)?)
     }
 }
 
 /// Secret source trait.
+///
+/// # Attributes
+/// The derive macro takes `keyring_entry = "..."` in `#[source(...)]` besides the path,
+/// and the same optional attributes as [`PersistSource`] except `env_prefix`, `version` and `migrations`.
 #[cfg(feature = "secret")]
-pub trait SecretSource: rom_cache::Cacheable + Serialize + DeserializeOwned {
+pub trait SecretSource: Cacheable + Serialize + DeserializeOwned {
     /// Path for the persist source.
     #[cfg(not(feature = "default_config_dir"))]
     const PATH: &'static str;
@@ -63,6 +293,12 @@ pub trait SecretSource: rom_cache::Cacheable + Serialize + DeserializeOwned {
     const NAME: &'static str;
     /// Keyring entry for the secret source.
     const KEYRING_ENTRY: &'static str;
+    /// Number of the backups kept before writing back, see [`backup`](crate::backup).
+    const BACKUPS: usize = 0;
+    /// How the file is locked against the other processes, see [`lock`](crate::lock).
+    const LOCK: LockMode = LockMode::Shared;
+    /// What to do if the file was modified by others since loaded, see [`conflict`](crate::conflict).
+    const CONFLICT: ConflictPolicy = ConflictPolicy::Merge;
 
     /// Path for the persist source.
     fn path() -> PathBuf {
@@ -77,32 +313,84 @@ pub trait SecretSource: rom_cache::Cacheable + Serialize + DeserializeOwned {
                 .join(Self::NAME)
         }
     }
-    /// Load the secret source.
+    /// Load the secret source, with the file locked.
+    /// If it cannot be decrypted and backups are kept, the newest valid backup is restored, see [`backup`](crate::backup).
     fn load() -> ::std::io::Result<Self> {
         let path = Self::path();
-        let encrypter =
-            Encrypter::new(Self::KEYRING_ENTRY).map_err(|_| std::io::ErrorKind::InvalidData)?;
-        let file = std::fs::File::open(path)?;
-        let encrypted: Vec<u8> = std::io::Read::bytes(file).collect::<Result<_, _>>()?;
-        encrypter
-            .decrypt(&encrypted)
-            .map_err(|_| std::io::ErrorKind::InvalidData.into())
-    }
-    /// Save the secret source.
+        let _lock = lock(&path, Self::LOCK, false)?;
+        let res = read_or_recover(&path, Self::BACKUPS, false, decrypt::<Self>);
+        record_loaded(&path, res.as_ref())?;
+        res
+    }
+    /// Read the secret source without recording it for detecting the conflicts.
+    /// This is used to get the values in the file before writing back.
+    fn read() -> ::std::io::Result<Self> {
+        let path = Self::path();
+        let _lock = lock(&path, Self::LOCK, false)?;
+        read_or_recover(&path, Self::BACKUPS, false, decrypt::<Self>)
+    }
+    /// Load the secret source again since the file changed.
+    /// Unlike [`SecretSource::load()`], a file which cannot be decrypted is returned as an error instead of recovered from the backups.
+    fn refresh() -> ::std::io::Result<Self> {
+        let path = Self::path();
+        let _lock = lock(&path, Self::LOCK, false)?;
+        let res = read_or_recover(&path, 0, false, decrypt::<Self>);
+        record_loaded(&path, res.as_ref())?;
+        res
+    }
+    /// Save the secret source, the file is locked and replaced atomically.
+    /// If the file was modified by others since loaded, it's resolved as [`SecretSource::CONFLICT`].
     fn store(&self) -> ::std::io::Result<()> {
-        use std::io::Write as _;
-
         let path = Self::path();
         let parent = path.parent().unwrap();
         std::fs::create_dir_all(parent)?;
-        let encrypter =
-            Encrypter::new(Self::KEYRING_ENTRY).map_err(|_| std::io::ErrorKind::InvalidData)?;
-        let encrypted = encrypter
-            .encrypt(self)
-            .map_err(|_| std::io::ErrorKind::InvalidData)?;
-        let mut file = std::fs::File::create(path)?;
-        file.write_all(&encrypted)?;
-        file.flush()?;
-        Ok(())
+        let encrypter = Encrypter::new(Self::KEYRING_ENTRY).map_err(std::io::Error::other)?;
+        let _lock = lock(&path, Self::LOCK, true)?;
+        let ours = serde_json::to_value(self)?;
+        let encrypted = match resolve(&path, Self::CONFLICT, &ours, |bytes| {
+            Ok(serde_json::to_value(decrypt::<Self>(bytes)?)?)
+        })? {
+            Resolved::Theirs => return Ok(()),
+            Resolved::Ours => encrypter.encrypt(self),
+            Resolved::Merged(merged) => encrypter.encrypt(&merged),
+        }
+        .map_err(|_| std::io::ErrorKind::InvalidData)?;
+        rotate(&path, Self::BACKUPS)?;
+        write_atomic(&path, &encrypted)?;
+        // The base is the cached value rather than the merged one, see `PersistSource::store()`.
+        record(&path, ours)
+    }
+}
+
+/// Parse the file of a persist source, upgrading it if versioned. Returns whether it was migrated.
+#[cfg(feature = "persist")]
+fn parse_persist<T: PersistSource>(bytes: &[u8]) -> std::io::Result<(T, bool)> {
+    if T::VERSION == 0 {
+        return Ok((serde_json::from_slice(bytes)?, false));
+    }
+    let value = serde_json::from_slice(bytes)?;
+    let (value, migrated) = migrate(value, T::VERSION, T::MIGRATIONS)?;
+    Ok((serde_json::from_value(value)?, migrated))
+}
+
+/// Decrypt the file of a secret source.
+#[cfg(feature = "secret")]
+fn decrypt<T: SecretSource>(encrypted: &[u8]) -> std::io::Result<T> {
+    let encrypter = Encrypter::new(T::KEYRING_ENTRY).map_err(std::io::Error::other)?;
+    encrypter
+        .decrypt(encrypted)
+        .map_err(|_| std::io::ErrorKind::InvalidData.into())
+}
+
+/// Record the value loaded from `path` for detecting the conflicts on writing back, the file not found is recorded too.
+#[cfg(feature = "persist")]
+fn record_loaded<T: Serialize>(
+    path: &Path,
+    loaded: Result<&T, &std::io::Error>,
+) -> std::io::Result<()> {
+    match loaded {
+        Ok(value) => record(path, serde_json::to_value(value)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => record(path, serde_json::Value::Null),
+        Err(_) => Ok(()),
     }
 }
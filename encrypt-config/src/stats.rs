@@ -0,0 +1,202 @@
+//! # Stats
+//! Counters of the cache, telling how often the sources are read or written.
+
+use crate::config::Config;
+use std::{
+    fmt::Write,
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// The counters of one config type, kept even if the cache line is evicted.
+pub(crate) struct Counters {
+    type_name: &'static str,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    loads: AtomicU64,
+    stores: AtomicU64,
+    store_failures: AtomicU64,
+    load_nanos: AtomicU64,
+    store_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn new(type_name: &'static str) -> Self {
+        Self {
+            type_name,
+            hits: Default::default(),
+            misses: Default::default(),
+            evictions: Default::default(),
+            loads: Default::default(),
+            stores: Default::default(),
+            store_failures: Default::default(),
+            load_nanos: Default::default(),
+            store_nanos: Default::default(),
+        }
+    }
+
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn evict(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Run `load`, counting it and the time spent whether it succeeded or not.
+    pub(crate) fn load<T>(&self, load: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        let (res, elapsed) = timed(load);
+        self.loads.fetch_add(1, Ordering::Relaxed);
+        self.load_nanos.fetch_add(elapsed, Ordering::Relaxed);
+        res
+    }
+
+    /// Run `store`, counting it as succeeded or failed and the time spent.
+    pub(crate) fn store(&self, store: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        let (res, elapsed) = timed(store);
+        match res {
+            Ok(()) => self.stores.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.store_failures.fetch_add(1, Ordering::Relaxed),
+        };
+        self.store_nanos.fetch_add(elapsed, Ordering::Relaxed);
+        res
+    }
+
+    fn snapshot(&self) -> CfgStats {
+        CfgStats {
+            type_name: self.type_name,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            loads: self.loads.load(Ordering::Relaxed),
+            stores: self.stores.load(Ordering::Relaxed),
+            store_failures: self.store_failures.load(Ordering::Relaxed),
+            load_time: Duration::from_nanos(self.load_nanos.load(Ordering::Relaxed)),
+            store_time: Duration::from_nanos(self.store_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn timed<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let start = Instant::now();
+    let res = f();
+    (
+        res,
+        start.elapsed().as_nanos().try_into().unwrap_or(u64::MAX),
+    )
+}
+
+/// The counters of one config type, see [`Config::stats()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CfgStats {
+    /// The type name of the config.
+    pub type_name: &'static str,
+    /// Times the value was retrieved from the cache.
+    pub hits: u64,
+    /// Times the value was not cached, so it was loaded from the source.
+    pub misses: u64,
+    /// Times the value was evicted to make room for others.
+    pub evictions: u64,
+    /// Times the source was read, including the failed ones falling back to default.
+    pub loads: u64,
+    /// Times the value was written back successfully.
+    pub stores: u64,
+    /// Times the value failed to be written back.
+    pub store_failures: u64,
+    /// Time spent in reading the source.
+    pub load_time: Duration,
+    /// Time spent in writing back, including the failed ones.
+    pub store_time: Duration,
+}
+
+impl<const N: usize> Config<N> {
+    /// The counters of each config type ever cached, sorted by the type name.
+    ///
+    /// The counters are kept even if the value is evicted or invalidated,
+    /// so they tell how often the files are re-read or the secrets are decrypted.
+    pub fn stats(&self) -> Vec<CfgStats> {
+        let mut stats = self
+            .cache
+            .counters()
+            .iter()
+            .map(|c| c.snapshot())
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|s| s.type_name);
+        stats
+    }
+
+    /// The counters of [`Config::stats()`] in Prometheus text exposition format,
+    /// labeled by the type name of each config.
+    ///
+    /// # Example
+    /// ```text
+    /// # HELP encrypt_config_cache_hits_total Times the value was retrieved from the cache.
+    /// # TYPE encrypt_config_cache_hits_total counter
+    /// encrypt_config_cache_hits_total{type="my_crate::MyConfig"} 42
+    /// ```
+    pub fn prometheus_stats(&self) -> String {
+        let stats = self.stats();
+        let mut out = String::new();
+        let mut metric = |name: &str, help: &str, value: fn(&CfgStats) -> String| {
+            writeln!(out, "# HELP encrypt_config_{name} {help}").ok();
+            writeln!(out, "# TYPE encrypt_config_{name} counter").ok();
+            for s in stats.iter() {
+                let label = s
+                    .type_name
+                    .replace('\\', r"\\")
+                    .replace('"', r#"\""#)
+                    .replace('\n', r"\n");
+                writeln!(
+                    out,
+                    "encrypt_config_{name}{{type=\"{label}\"}} {}",
+                    value(s)
+                )
+                .ok();
+            }
+        };
+        metric(
+            "cache_hits_total",
+            "Times the value was retrieved from the cache.",
+            |s| s.hits.to_string(),
+        );
+        metric(
+            "cache_misses_total",
+            "Times the value was not cached.",
+            |s| s.misses.to_string(),
+        );
+        metric(
+            "cache_evictions_total",
+            "Times the value was evicted.",
+            |s| s.evictions.to_string(),
+        );
+        metric("loads_total", "Times the source was read.", |s| {
+            s.loads.to_string()
+        });
+        metric(
+            "stores_total",
+            "Times the value was written back successfully.",
+            |s| s.stores.to_string(),
+        );
+        metric(
+            "store_failures_total",
+            "Times the value failed to be written back.",
+            |s| s.store_failures.to_string(),
+        );
+        metric(
+            "load_seconds_total",
+            "Time spent in reading the source.",
+            |s| s.load_time.as_secs_f64().to_string(),
+        );
+        metric("store_seconds_total", "Time spent in writing back.", |s| {
+            s.store_time.as_secs_f64().to_string()
+        });
+        out
+    }
+}
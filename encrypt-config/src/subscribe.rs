@@ -0,0 +1,133 @@
+//! # Subscribe
+//! React to the changes of the cached values.
+
+use crate::{config::Config, source::Cacheable};
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+type Callback = Arc<dyn Fn(&dyn Any) + Send + Sync>;
+
+/// The subscribers of one config type.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    next_id: AtomicUsize,
+    callbacks: Mutex<Vec<(usize, Callback)>>,
+}
+
+impl Subscribers {
+    fn subscribe<T: Any>(&self, f: impl Fn(&T) + Send + Sync + 'static) -> usize {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let callback: Callback = Arc::new(move |value: &dyn Any| {
+            if let Some(value) = value.downcast_ref::<T>() {
+                f(value)
+            }
+        });
+        self.lock().push((id, callback));
+        id
+    }
+
+    fn unsubscribe(&self, id: usize) {
+        self.lock().retain(|(i, _)| *i != id);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Call the callbacks. They are cloned out first, so that subscribing or unsubscribing in callbacks is allowed.
+    pub(crate) fn notify(&self, value: &dyn Any) {
+        let callbacks = self.lock().clone();
+        for (_, callback) in callbacks {
+            callback(value);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(usize, Callback)>> {
+        self.callbacks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A value evicted from a full [`Config`] with a fixed capacity, see [`Config::on_evict()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CfgEvicted {
+    /// The type name of the value evicted.
+    pub type_name: &'static str,
+}
+
+/// A guard of the subscription, unsubscribes when dropped.
+///
+/// See [`Config::subscribe()`] for more details.
+#[must_use = "the callback is unsubscribed immediately if the guard is dropped"]
+pub struct CfgSubscription {
+    subscribers: Arc<Subscribers>,
+    id: usize,
+}
+
+impl CfgSubscription {
+    /// Unsubscribe, this is the same as dropping the guard.
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for CfgSubscription {
+    fn drop(&mut self) {
+        self.subscribers.unsubscribe(self.id);
+    }
+}
+
+impl<const N: usize> Config<N> {
+    /// Subscribe the changes of `T`, the callback is called with the new value:
+    /// - after a [`CfgMut`](crate::config::CfgMut) mutably dereferenced is released.
+    /// - after `T` is reloaded or refreshed from the source.
+    /// - after `T` is written back by flushing.
+    ///
+    /// The callback is called with a read flag held, so [`Config::get()`] of `T` is allowed in it,
+    /// while [`Config::get_mut()`] of `T` would panic.
+    /// Callbacks are called on the thread releasing the borrow or flushing.
+    ///
+    /// Returns a [`CfgSubscription`] guard, the callback is unsubscribed when it's dropped.
+    pub fn subscribe<T>(&self, f: impl Fn(&T) + Send + Sync + 'static) -> CfgSubscription
+    where
+        T: Cacheable,
+    {
+        let subscribers = self.cache.subscribers::<T>();
+        let id = subscribers.subscribe(f);
+        CfgSubscription { subscribers, id }
+    }
+
+    /// Subscribe the evictions, the callback is called when a value is evicted since the config is full,
+    /// after written back if dirty. `Config<0>` (aka [`DynConfig`](crate::DynConfig)) never evicts.
+    ///
+    /// The callback is called after the cache is unlocked, so accessing the config in it is allowed.
+    /// Callbacks are called on the thread evicting, i.e. retrieving or inserting another value.
+    ///
+    /// Returns a [`CfgSubscription`] guard, the callback is unsubscribed when it's dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "derive")]
+    /// # {
+    /// use encrypt_config::{Config, NormalSource};
+    ///
+    /// #[derive(Default, NormalSource)]
+    /// struct A;
+    /// #[derive(Default, NormalSource)]
+    /// struct B;
+    ///
+    /// let cfg: Config<1> = Config::new();
+    /// let _evictions = cfg.on_evict(|evicted| eprintln!("`{}` evicted", evicted.type_name));
+    /// cfg.get::<A>();
+    /// cfg.get::<B>(); // `A` evicted
+    /// # }
+    /// ```
+    pub fn on_evict(&self, f: impl Fn(&CfgEvicted) + Send + Sync + 'static) -> CfgSubscription {
+        let subscribers = self.cache.evictions();
+        let id = subscribers.subscribe(f);
+        CfgSubscription { subscribers, id }
+    }
+}
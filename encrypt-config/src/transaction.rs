@@ -0,0 +1,229 @@
+//! # Transaction
+//! Commit changes to several sources all-or-nothing.
+
+#[cfg(feature = "persist")]
+use crate::conflict;
+use crate::{
+    config::{CfgMut, Config},
+    error::{from_source_error, CommitFailed, ConfigResult, StoreFailed},
+    source::Cacheable,
+};
+use snafu::IntoError;
+use std::{
+    any::{type_name, Any},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A transaction staging changes to several sources, see [`Config::transaction()`].
+pub struct Transaction<'a, const N: usize> {
+    config: &'a Config<N>,
+    staged: Vec<Box<dyn Staged + 'a>>,
+}
+
+impl<'a, const N: usize> Transaction<'a, N> {
+    /// Get a mutable ref of the staged value of `T`, which is cloned from the cached value on first access.
+    /// `T` is mutably borrowed until the transaction ends.
+    ///
+    /// See [`Config::try_get_mut()`] for the errors.
+    pub fn get_mut<T>(&mut self) -> ConfigResult<&mut T>
+    where
+        T: Cacheable + Default + Clone,
+    {
+        let i = match self.staged.iter().position(|s| s.value().is::<T>()) {
+            Some(i) => i,
+            None => {
+                let guard = self.config.try_get_mut::<T>()?;
+                let value = T::clone(&guard);
+                self.staged.push(Box::new(Entry {
+                    guard,
+                    value,
+                    original: None,
+                }));
+                self.staged.len() - 1
+            }
+        };
+        Ok(self.staged[i]
+            .value_mut()
+            .downcast_mut::<T>()
+            .expect("downcast failed"))
+    }
+
+    /// Write back all the staged values, the ones written are rolled back if any failed.
+    fn commit(mut self) -> ConfigResult<()> {
+        for i in 0..self.staged.len() {
+            if let Err(e) = self.staged[i].store() {
+                let rollback_errors = self.staged[..i]
+                    .iter()
+                    .filter_map(|s| s.rollback().err())
+                    .collect::<Vec<_>>();
+                return Err(CommitFailed { rollback_errors }.into_error(Box::new(e)));
+            }
+        }
+        self.staged.into_iter().for_each(|s| s.apply());
+        Ok(())
+    }
+}
+
+impl<const N: usize> Config<N> {
+    /// Run `f` in a transaction, changes are staged and then committed all-or-nothing.
+    ///
+    /// - If `f` returns an error, staged changes are discarded and nothing is written.
+    /// - If `f` returns `Ok`, the staged values are written back one by one. If any of them failed,
+    ///   the files written are restored to what they were before committing (removed if they did not exist),
+    ///   the sources not backed by a file are rolled back to the cached values, and the cached values are left untouched.
+    /// - Once committed, the cached values are replaced and marked clean.
+    ///
+    /// Each type staged is mutably borrowed until the transaction ends,
+    /// so make sure the capacity `N` is enough for them.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "derive")]
+    /// # {
+    /// # use encrypt_config::{Config, NormalSource};
+    /// # #[derive(Default, Clone, NormalSource)]
+    /// # struct A { value: i32 }
+    /// # #[derive(Default, Clone, NormalSource)]
+    /// # struct B { value: i32 }
+    /// let cfg: Config<2> = Config::new();
+    /// cfg.transaction(|tx| {
+    ///     tx.get_mut::<A>()?.value = 1;
+    ///     tx.get_mut::<B>()?.value = 2;
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// - Errors returned by `f`.
+    /// - [`ConfigError::CommitFailed`](crate::error::ConfigError::CommitFailed): some staged values cannot be written back.
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&mut Transaction<'_, N>) -> ConfigResult<R>,
+    ) -> ConfigResult<R> {
+        let mut tx = Transaction {
+            config: self,
+            staged: vec![],
+        };
+        let res = f(&mut tx)?;
+        tx.commit()?;
+        Ok(res)
+    }
+}
+
+/// A staged change of one type.
+trait Staged {
+    fn value(&self) -> &dyn Any;
+    fn value_mut(&mut self) -> &mut dyn Any;
+    /// Write back the staged value, the original file is captured before writing.
+    fn store(&mut self) -> ConfigResult<()>;
+    /// Restore the original file, or write back the cached value if not backed by a file.
+    fn rollback(&self) -> ConfigResult<()>;
+    /// Replace the cached value with the staged one.
+    fn apply(self: Box<Self>);
+}
+
+struct Entry<'a, T> {
+    guard: CfgMut<'a, T>,
+    value: T,
+    /// `None` if not backed by a file, or not written yet.
+    original: Option<Original>,
+}
+
+/// The file behind a source before committing, restored exactly on rolling back.
+struct Original {
+    path: PathBuf,
+    /// `None` if the file did not exist.
+    contents: Option<Vec<u8>>,
+    /// The state recorded for detecting the modification by others.
+    #[cfg(feature = "persist")]
+    record: conflict::Saved,
+}
+
+impl Original {
+    fn capture(path: PathBuf) -> io::Result<Self> {
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => Some(contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            #[cfg(feature = "persist")]
+            record: conflict::save(&path),
+            path,
+            contents,
+        })
+    }
+
+    fn restore(&self) -> io::Result<()> {
+        match &self.contents {
+            Some(contents) => write(&self.path, contents)?,
+            None => match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            },
+        }
+        #[cfg(feature = "persist")]
+        conflict::restore(&self.path, self.record.clone());
+        Ok(())
+    }
+}
+
+fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    #[cfg(feature = "persist")]
+    return crate::file::write_atomic(path, contents);
+    #[cfg(not(feature = "persist"))]
+    std::fs::write(path, contents)
+}
+
+impl<T: Cacheable> Staged for Entry<'_, T> {
+    fn value(&self) -> &dyn Any {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut dyn Any {
+        &mut self.value
+    }
+
+    fn store(&mut self) -> ConfigResult<()> {
+        let counters = self.guard.counters();
+        counters
+            .store(|| {
+                self.original = T::source_path().map(Original::capture).transpose()?;
+                self.value.store()
+            })
+            .map_err(|e| {
+                from_source_error(e, |e| {
+                    StoreFailed {
+                        type_name: type_name::<T>(),
+                    }
+                    .into_error(e)
+                })
+            })
+    }
+
+    fn rollback(&self) -> ConfigResult<()> {
+        let counters = self.guard.counters();
+        let res = match &self.original {
+            Some(original) => original.restore(),
+            None => counters.store(|| self.guard.store()),
+        };
+        res.map_err(|e| {
+            from_source_error(e, |e| {
+                StoreFailed {
+                    type_name: type_name::<T>(),
+                }
+                .into_error(e)
+            })
+        })
+    }
+
+    fn apply(self: Box<Self>) {
+        let Entry {
+            mut guard, value, ..
+        } = *self;
+        guard.set_stored(value);
+    }
+}
@@ -0,0 +1,88 @@
+//! # Validate
+//! Check the config values on loading and before writing back.
+//!
+//! Use `#[source(validate = path::to::fn)]` on the derive macros of `PersistSource` and `SecretSource`,
+//! where the function takes `&Self` and returns `Result<(), Vec<FieldError>>`.
+//! Or implement [`Validate`] and use `#[source(validate = Self::validate)]`.
+//! Or derive [`Validate`] with the field rules in `#[config(...)]`, which is used if `validate` is not given.
+//!
+//! Failures surface as [`ConfigError::ValidationFailed`](crate::error::ConfigError::ValidationFailed)
+//! from [`Config::reload()`](crate::Config::reload), [`Config::flush()`](crate::Config::flush) and the others writing back.
+//! A value failed on loading is never cached, and [`Config::try_get()`](crate::Config::try_get) returns the error on cache miss.
+
+use crate::error::ValidationFailed;
+use std::{any::type_name, fmt, io};
+
+/// A type whose values can be validated.
+pub trait Validate {
+    /// Check the value, returning the errors of all invalid fields.
+    fn validate(&self) -> Result<(), Vec<FieldError>>;
+}
+
+/// The error of an invalid field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The path of the field, e.g. `database.port`.
+    pub field: String,
+    /// Why the field is invalid.
+    pub message: String,
+}
+
+impl FieldError {
+    /// Create a [`FieldError`].
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", self.field, self.message)
+    }
+}
+
+/// Check `value` by `validate`, the errors are carried by an io error so that they can be returned from
+/// [`Cacheable::load()`](crate::Cacheable::load) and [`Cacheable::store()`](crate::Cacheable::store).
+/// This is used by the derive macros.
+pub fn ensure<T>(
+    value: &T,
+    validate: impl FnOnce(&T) -> Result<(), Vec<FieldError>>,
+) -> io::Result<()> {
+    validate(value).map_err(|errors| {
+        io::Error::other(
+            ValidationFailed {
+                type_name: type_name::<T>(),
+                errors,
+            }
+            .build(),
+        )
+    })
+}
+
+/// Whether `value` matches `pattern`, the compiled regexes are cached. This is used by the derive macro of `Validate`,
+/// which rejects the invalid patterns at compile time.
+///
+/// # Panic
+/// - If `pattern` is invalid.
+#[cfg(feature = "regex")]
+pub fn is_match(pattern: &'static str, value: &str) -> bool {
+    use std::{
+        collections::HashMap,
+        sync::{Mutex, OnceLock},
+    };
+
+    static REGEXES: OnceLock<Mutex<HashMap<&'static str, regex::Regex>>> = OnceLock::new();
+    let mut regexes = REGEXES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    regexes
+        .entry(pattern)
+        .or_insert_with(|| {
+            regex::Regex::new(pattern).unwrap_or_else(|e| panic!("invalid regex `{pattern}`: {e}"))
+        })
+        .is_match(value)
+}
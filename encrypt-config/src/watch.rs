@@ -0,0 +1,79 @@
+//! # Watch
+//! Hot reload the cached values when the files behind the sources changed.
+
+use crate::{
+    config::Config,
+    error::{ConfigResult, Unwatchable, WatchFailed},
+    source::Cacheable,
+};
+use notify::{
+    event::{AccessKind, AccessMode},
+    EventKind, RecursiveMode, Watcher as _,
+};
+use snafu::{OptionExt, ResultExt};
+use std::{any::type_name, path::Path};
+
+/// A watcher refreshing the cached value when the file behind the source changed.
+/// Watching stops when it's dropped.
+///
+/// See [`Config::watch()`] for more details.
+pub struct CfgWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl<const N: usize> Config<N> {
+    /// Watch the file behind `T` ([`PersistSource::path()`](crate::PersistSource::path()) or
+    /// `SecretSource::path()`), refreshing the cached value when the file changed.
+    ///
+    /// - The new content is loaded and parsed first, a bad edit never replaces the cached value.
+    ///   It's loaded by [`Cacheable::refresh()`], so a corrupt file is neither moved aside nor recovered from the backups.
+    /// - If the cached value is dirty, the changes in memory win, and will overwrite the file when written back.
+    /// - If a [`CfgRef`](crate::config::CfgRef) or [`CfgMut`](crate::config::CfgMut) of `T` is alive,
+    ///   the value is refreshed on next retrieving.
+    ///
+    /// # Errors
+    /// - [`ConfigError::Unwatchable`](crate::error::ConfigError::Unwatchable): `T` is not backed by a file.
+    /// - [`ConfigError::WatchFailed`](crate::error::ConfigError::WatchFailed): the watcher cannot be set up.
+    pub fn watch<T>(&self) -> ConfigResult<CfgWatcher>
+    where
+        T: Cacheable,
+    {
+        let path = T::source_path().context(Unwatchable {
+            type_name: type_name::<T>(),
+        })?;
+        // Watch the parent dir, since the file may be replaced instead of modified in place.
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)?;
+        let file_name = path.file_name().map(ToOwned::to_owned);
+        let cache = self.cache.downgrade();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            let changed = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Modify(_)
+                    | EventKind::Access(AccessKind::Close(AccessMode::Write))
+            );
+            if changed
+                && event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == file_name.as_deref())
+            {
+                if let Some(cache) = cache.upgrade() {
+                    cache.refresh::<T>();
+                }
+            }
+        })
+        .context(WatchFailed)?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .context(WatchFailed)?;
+        Ok(CfgWatcher { _watcher: watcher })
+    }
+}
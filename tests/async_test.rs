@@ -0,0 +1,88 @@
+use encrypt_config::{AsyncConfig, PersistSource};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    sync::Mutex,
+    thread::{self, ThreadId},
+};
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "async_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/async_config.json"))
+)]
+struct AsyncPersistConfig {
+    value: i32,
+}
+
+#[test]
+fn async_test() {
+    std::fs::remove_file(AsyncPersistConfig::path()).ok();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let cfg: AsyncConfig<1> = AsyncConfig::new();
+        assert_eq!(cfg.get::<AsyncPersistConfig>().await.unwrap().value, 0);
+        cfg.get_mut::<AsyncPersistConfig>().await.unwrap().value = 42;
+        cfg.flush_all().await.unwrap();
+        assert_eq!(AsyncPersistConfig::load().unwrap().value, 42);
+    });
+    rt.block_on(async {
+        let cfg: AsyncConfig<1> = AsyncConfig::new();
+        assert_eq!(cfg.get::<AsyncPersistConfig>().await.unwrap().value, 42);
+        cfg.get_mut::<AsyncPersistConfig>().await.unwrap().value = 7;
+        cfg.flush::<AsyncPersistConfig>().await.unwrap();
+        assert_eq!(AsyncPersistConfig::load().unwrap().value, 7);
+    });
+    std::fs::remove_file(AsyncPersistConfig::path()).ok();
+}
+
+/// The threads loading [`ThreadConfig`].
+static LOADED_ON: Mutex<Vec<ThreadId>> = Mutex::new(vec![]);
+
+#[derive(Default)]
+struct ThreadConfig;
+
+impl encrypt_config::Cacheable for ThreadConfig {
+    fn load() -> std::io::Result<Self> {
+        LOADED_ON.lock().unwrap().push(thread::current().id());
+        Ok(Self)
+    }
+
+    fn store(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Default, encrypt_config::NormalSource)]
+struct OtherConfig;
+
+#[test]
+fn off_executor_test() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let executor = thread::current().id();
+    rt.block_on(async {
+        let cfg: AsyncConfig<1> = AsyncConfig::new();
+        cfg.get::<ThreadConfig>().await.unwrap();
+        // evicted, then loaded again
+        cfg.get::<OtherConfig>().await.unwrap();
+        cfg.get_mut::<ThreadConfig>().await.unwrap();
+        cfg.blocking().invalidate::<ThreadConfig>().unwrap();
+        cfg.get::<ThreadConfig>().await.unwrap();
+    });
+    let loaded_on = LOADED_ON.lock().unwrap();
+    assert_eq!(loaded_on.len(), 3);
+    assert!(loaded_on.iter().all(|id| *id != executor));
+}
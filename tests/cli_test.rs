@@ -0,0 +1,79 @@
+use clap::{Command, Parser};
+use encrypt_config::{cli, CliSource, Config, PersistSource};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default, PersistSource, CliSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "cli_config.json", cli))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/cli_config.json"), cli)
+)]
+struct CliConfig {
+    /// The log level.
+    log_level: String,
+    verbose: bool,
+    port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u32>,
+}
+
+#[derive(Parser, Serialize)]
+struct Args {
+    #[arg(long)]
+    port: Option<u16>,
+    #[arg(long)]
+    log_level: Option<String>,
+    #[arg(long)]
+    timeout: Option<u32>,
+}
+
+fn matches(args: &[&str]) -> clap::ArgMatches {
+    CliConfig::command(Command::new("myapp"))
+        .try_get_matches_from(args)
+        .unwrap()
+}
+
+#[test]
+fn cli_test() {
+    std::fs::remove_file(CliConfig::path()).ok();
+    CliConfig::set_overrides(&matches(&["myapp", "--log-level", "debug", "--verbose"])).unwrap();
+    {
+        let cfg: Config<1> = Config::default();
+        let mut cli = cfg.get_mut::<CliConfig>();
+        assert_eq!(cli.log_level, "debug");
+        assert!(cli.verbose);
+        cli.port = 8080;
+    }
+    // the overrides are not written back
+    let cli = <CliConfig as PersistSource>::load().unwrap();
+    assert_eq!(cli.log_level, "");
+    assert!(!cli.verbose);
+    assert_eq!(cli.port, 8080);
+
+    let err = CliConfig::set_overrides(&matches(&["myapp", "--port", "abc"])).unwrap_err();
+    assert!(err.to_string().contains("--port abc"));
+
+    cli::set_overrides_from::<CliConfig, _>(&Args::parse_from([
+        "myapp",
+        "--port",
+        "9090",
+        "--timeout",
+        "30",
+    ]))
+    .unwrap();
+    {
+        let cfg: Config<1> = Config::default();
+        let mut cli = cfg.get_mut::<CliConfig>();
+        assert_eq!(cli.log_level, "");
+        assert_eq!(cli.port, 9090);
+        assert_eq!(cli.timeout, Some(30));
+        cli.verbose = true;
+    }
+    // the overrides absent in the file are not written back either
+    let cli = <CliConfig as PersistSource>::load().unwrap();
+    assert_eq!(cli.timeout, None);
+    assert!(cli.verbose);
+    cli::clear_overrides::<CliConfig>();
+    assert_eq!(Config::<1>::default().get::<CliConfig>().port, 8080);
+    std::fs::remove_file(CliConfig::path()).ok();
+}
@@ -0,0 +1,192 @@
+//! The tests setting the environment variables, kept apart from the other tests which read them concurrently,
+//! and serialized by [`lock_env()`].
+
+use encrypt_config::{
+    Cacheable, Config, Fallback, Layer, LayeredSource, NormalSource, PersistSource, TEST_OUT_DIR,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, MutexGuard};
+
+/// Hold this while setting or reading the environment variables.
+fn lock_env() -> MutexGuard<'static, ()> {
+    static ENV: Mutex<()> = Mutex::new(());
+    ENV.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+const LAYERED_SYSTEM: &str = const_str::concat!(TEST_OUT_DIR, "/layered_system.json");
+const LAYERED_USER: &str = const_str::concat!(TEST_OUT_DIR, "/layered_user.json");
+
+#[derive(Serialize, Deserialize, Default, LayeredSource)]
+#[source(layers = vec![
+    Layer::value(&LayeredConfig { name: "default".into(), port: 80, ..Default::default() }),
+    Layer::file(LAYERED_SYSTEM),
+    Layer::writable_file(LAYERED_USER),
+    Layer::env("LAYERED_TEST_"),
+    Layer::args(["--verbose", "--port", "8080"]),
+])]
+struct LayeredConfig {
+    name: String,
+    port: u16,
+    verbose: bool,
+    user: String,
+}
+
+#[test]
+fn layered_test() {
+    let _env = lock_env();
+    std::fs::create_dir_all(TEST_OUT_DIR).unwrap();
+    std::fs::write(LAYERED_SYSTEM, r#"{"name": "system", "port": 81}"#).unwrap();
+    std::fs::remove_file(LAYERED_USER).ok();
+    std::env::set_var("LAYERED_TEST_NAME", "env");
+    let cfg: Config<1> = Config::default();
+    {
+        let layered = cfg.get::<LayeredConfig>();
+        assert_eq!(layered.name, "env");
+        assert_eq!(layered.port, 8080);
+        assert!(layered.verbose);
+        assert_eq!(layered.user, "");
+    }
+    cfg.get_mut::<LayeredConfig>().user = "kw".to_owned();
+    cfg.flush::<LayeredConfig>().unwrap();
+    // only the changed field is written to the writable layer
+    let user = std::fs::read_to_string(LAYERED_USER).unwrap();
+    assert_eq!(user, r#"{"user":"kw"}"#);
+    cfg.reload::<LayeredConfig>().unwrap();
+    assert_eq!(cfg.get::<LayeredConfig>().user, "kw");
+    assert_eq!(
+        cfg.entries()[0].path.as_deref(),
+        Some(LAYERED_USER.as_ref())
+    );
+    // the names not valid unicode are skipped instead of panicking
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        std::env::set_var(OsStr::from_bytes(b"LAYERED_TEST_\xff"), "1");
+        cfg.reload::<LayeredConfig>().unwrap();
+        assert_eq!(cfg.get::<LayeredConfig>().name, "env");
+        std::env::remove_var(OsStr::from_bytes(b"LAYERED_TEST_\xff"));
+    }
+    std::fs::remove_file(LAYERED_SYSTEM).ok();
+    std::fs::remove_file(LAYERED_USER).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "env_config.json", env_prefix = "ENV_TEST_")
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/env_config.json"), env_prefix = "ENV_TEST_")
+)]
+struct EnvConfig {
+    name: String,
+    database: Database,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Database {
+    host: String,
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, Default, NormalSource)]
+#[source(env_prefix = "ENV_NORMAL_TEST_")]
+struct EnvNormalConfig {
+    verbose: bool,
+}
+
+#[test]
+fn env_test() {
+    let _env = lock_env();
+    std::fs::remove_file(EnvConfig::path()).ok();
+    std::env::set_var("ENV_TEST_NAME", "42");
+    std::env::set_var("ENV_TEST_DATABASE__PORT", "5433");
+    std::env::set_var("ENV_TEST_TIMEOUT", "30");
+    std::env::set_var("ENV_NORMAL_TEST_VERBOSE", "true");
+    {
+        let cfg: Config<2> = Config::default();
+        assert!(cfg.get::<EnvNormalConfig>().verbose);
+        let mut env = cfg.get_mut::<EnvConfig>();
+        assert_eq!(env.name, "42");
+        assert_eq!(env.database.port, 5433);
+        assert_eq!(env.timeout, Some(30));
+        env.database.host = "localhost".to_owned();
+    }
+    // the overlaid values are not written back
+    let env = <EnvConfig as PersistSource>::load().unwrap();
+    assert_eq!(env.name, "");
+    assert_eq!(env.database.port, 0);
+    assert_eq!(env.database.host, "localhost");
+    assert_eq!(env.timeout, None);
+
+    std::env::set_var("ENV_TEST_DATABASE__PORT", "not a port");
+    let err = <EnvConfig as encrypt_config::Cacheable>::load()
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("ENV_TEST_DATABASE__PORT"));
+    std::env::set_var("ENV_TEST_DATABASE__PORT", "5433");
+
+    // the names not valid unicode are skipped, and the values are errors instead of panicking
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let invalid = OsStr::from_bytes(b"\xff");
+        std::env::set_var(OsStr::from_bytes(b"ENV_TEST_\xff"), "1");
+        let env = <EnvConfig as encrypt_config::Cacheable>::load().unwrap();
+        assert_eq!(env.database.port, 5433);
+        std::env::remove_var(OsStr::from_bytes(b"ENV_TEST_\xff"));
+        std::env::set_var("ENV_TEST_NAME", invalid);
+        let err = <EnvConfig as encrypt_config::Cacheable>::load()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("ENV_TEST_NAME"));
+    }
+    std::env::remove_var("ENV_TEST_NAME");
+    std::env::remove_var("ENV_TEST_DATABASE__PORT");
+    std::env::remove_var("ENV_TEST_TIMEOUT");
+    std::env::remove_var("ENV_NORMAL_TEST_VERBOSE");
+    std::fs::remove_file(EnvConfig::path()).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "env_never_config.json", env_prefix = "ENV_NEVER_TEST_", fallback = Fallback::Never)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/env_never_config.json"), env_prefix = "ENV_NEVER_TEST_", fallback = Fallback::Never)
+)]
+struct EnvNeverConfig {
+    name: String,
+    port: u16,
+}
+
+#[test]
+fn env_create_test() {
+    let _env = lock_env();
+    let path = EnvNeverConfig::path();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::remove_file(&path).ok();
+    std::env::set_var("ENV_NEVER_TEST_NAME", "env");
+    assert!(<EnvNeverConfig as Cacheable>::load().is_err());
+    // the file is created even if never falling back, without the overlaid values
+    let cfg: Config<1> = Config::default();
+    cfg.insert(EnvNeverConfig {
+        name: "env".into(),
+        port: 0,
+    })
+    .unwrap();
+    cfg.get_mut::<EnvNeverConfig>().port = 8080;
+    cfg.flush::<EnvNeverConfig>().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"{"name":"","port":8080}"#
+    );
+    cfg.invalidate::<EnvNeverConfig>().unwrap();
+    std::env::remove_var("ENV_NEVER_TEST_NAME");
+    std::fs::remove_file(&path).ok();
+}
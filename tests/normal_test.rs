@@ -1,6 +1,14 @@
-use encrypt_config::{Config, NormalSource};
+use encrypt_config::{
+    error::ConfigError, BorrowState, Cacheable, Config, DynConfig, FieldError, NormalSource,
+    SourceKind, Validate,
+};
+use std::sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
 
-#[derive(Default, NormalSource)]
+#[derive(Default, Clone, NormalSource)]
 struct NormalConfig {
     value: i32,
 }
@@ -28,3 +36,300 @@ fn write_while_reading() {
     let _normal_ref = cfg.get::<NormalConfig>();
     let _normal_mut = cfg.get_mut::<NormalConfig>();
 }
+
+#[test]
+fn try_get_test() {
+    let cfg: Config<1> = Config::default();
+    let _normal_ref = cfg.try_get::<NormalConfig>().unwrap();
+    assert!(matches!(
+        cfg.try_get_mut::<NormalConfig>(),
+        Err(ConfigError::AlreadyBorrowed { .. })
+    ));
+    assert!(cfg.try_get_many::<(NormalConfig,)>().is_ok());
+    assert!(cfg.try_get_mut_many::<(NormalConfig,)>().is_err());
+}
+
+#[test]
+fn get_mut_dirty_test() {
+    let cfg: Config<1> = Config::default();
+    cfg.get_mut::<NormalConfig>().value = 42;
+    // The value is marked dirty now, but not being written.
+    cfg.get_mut::<NormalConfig>().value += 1;
+    assert_eq!(cfg.get::<NormalConfig>().value, 43);
+}
+
+#[derive(Default)]
+struct ReadOnlyConfig {
+    value: i32,
+}
+
+impl Cacheable for ReadOnlyConfig {
+    fn load() -> std::io::Result<Self> {
+        Ok(Self::default())
+    }
+
+    fn store(&self) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn flush_test() {
+    let cfg: Config<2> = Config::default();
+    cfg.get_mut::<NormalConfig>().value = 42;
+    cfg.get_mut::<ReadOnlyConfig>().value = 42;
+    assert!(cfg.flush::<NormalConfig>().is_ok());
+    assert!(matches!(
+        cfg.flush::<ReadOnlyConfig>(),
+        Err(ConfigError::StoreFailed { .. })
+    ));
+    {
+        let _normal_mut = cfg.get_mut::<NormalConfig>();
+        let Err(ConfigError::FlushFailed { errors }) = cfg.flush_all() else {
+            panic!("flush_all should fail");
+        };
+        // NormalConfig is clean, so only ReadOnlyConfig failed.
+        assert_eq!(errors.len(), 1);
+    }
+    assert_eq!(cfg.get::<ReadOnlyConfig>().value, 42);
+}
+
+#[test]
+fn subscribe_test() {
+    let cfg: Config<1> = Config::default();
+    let seen = Arc::new(AtomicI32::new(0));
+    let subscription = cfg.subscribe::<NormalConfig>({
+        let seen = seen.clone();
+        move |normal| seen.store(normal.value, Ordering::Relaxed)
+    });
+    cfg.get_mut::<NormalConfig>().value = 42;
+    assert_eq!(seen.load(Ordering::Relaxed), 42);
+    // not changed
+    let _ = cfg.get_mut::<NormalConfig>();
+    seen.store(0, Ordering::Relaxed);
+    cfg.flush::<NormalConfig>().unwrap();
+    assert_eq!(seen.load(Ordering::Relaxed), 42);
+    subscription.unsubscribe();
+    cfg.get_mut::<NormalConfig>().value = 7;
+    assert_eq!(seen.load(Ordering::Relaxed), 42);
+}
+
+#[test]
+fn transaction_test() {
+    let cfg: Config<2> = Config::default();
+    // aborted
+    let res = cfg.transaction(|tx| {
+        tx.get_mut::<NormalConfig>()?.value = 42;
+        cfg.try_get::<NormalConfig>()?;
+        Ok(())
+    });
+    assert!(matches!(res, Err(ConfigError::AlreadyBorrowed { .. })));
+    assert_eq!(cfg.get::<NormalConfig>().value, 0);
+    // committed
+    cfg.transaction(|tx| {
+        tx.get_mut::<NormalConfig>()?.value = 42;
+        assert_eq!(tx.get_mut::<NormalConfig>()?.value, 42);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(cfg.get::<NormalConfig>().value, 42);
+}
+
+#[test]
+fn snapshot_test() {
+    let cfg: Config<1> = Config::default();
+    let old = cfg.snapshot::<NormalConfig>();
+    assert!(Arc::ptr_eq(&old, &cfg.snapshot::<NormalConfig>()));
+    {
+        let mut normal = cfg.get_mut::<NormalConfig>();
+        normal.value = 42;
+        assert_eq!(cfg.snapshot::<NormalConfig>().value, 0);
+    }
+    assert_eq!(old.value, 0);
+    assert_eq!(cfg.snapshot::<NormalConfig>().value, 42);
+}
+
+#[test]
+fn blocking_test() {
+    let cfg: Config<1> = Config::default();
+    let normal = cfg.get::<NormalConfig>();
+    let res = cfg.get_mut_timeout::<NormalConfig>(Duration::from_millis(10));
+    assert!(matches!(res, Err(ConfigError::AlreadyBorrowed { .. })));
+    std::thread::scope(|s| {
+        let writer = s.spawn(|| cfg.get_mut_blocking::<NormalConfig>().value = 42);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(normal.value, 0);
+        drop(normal);
+        writer.join().unwrap();
+    });
+    let normal = cfg.get_timeout::<NormalConfig>(Duration::from_millis(10));
+    assert_eq!(normal.unwrap().value, 42);
+    assert_eq!(cfg.get_blocking::<NormalConfig>().value, 42);
+}
+
+#[derive(Default, NormalSource)]
+struct AnotherConfig;
+
+#[test]
+fn capacity_test() {
+    let cfg: Config<1> = Config::default();
+    let evicted = Arc::new(Mutex::new(vec![]));
+    let evictions = cfg.on_evict({
+        let evicted = evicted.clone();
+        move |e| evicted.lock().unwrap().push(e.type_name)
+    });
+    cfg.get_mut::<NormalConfig>().value = 42;
+    // NormalConfig is evicted, and its changes are not persisted
+    cfg.get::<AnotherConfig>();
+    assert_eq!(cfg.get::<NormalConfig>().value, 0);
+    assert_eq!(
+        *evicted.lock().unwrap(),
+        [
+            std::any::type_name::<NormalConfig>(),
+            std::any::type_name::<AnotherConfig>()
+        ]
+    );
+    drop(evictions);
+
+    let cfg = DynConfig::default();
+    let _evictions = cfg.on_evict(|e| panic!("`{}` evicted", e.type_name));
+    cfg.get_mut::<NormalConfig>().value = 42;
+    let _another = cfg.get::<AnotherConfig>();
+    let _read_only = cfg.get::<ReadOnlyConfig>();
+    assert_eq!(cfg.get::<NormalConfig>().value, 42);
+}
+
+#[test]
+fn stats_test() {
+    let cfg: Config<1> = Config::default();
+    cfg.get_mut::<NormalConfig>().value = 42;
+    cfg.get::<NormalConfig>();
+    cfg.get::<AnotherConfig>();
+    cfg.get::<NormalConfig>();
+    let stats = cfg.stats();
+    let normal = stats
+        .iter()
+        .find(|s| s.type_name == std::any::type_name::<NormalConfig>())
+        .unwrap();
+    assert_eq!(
+        (normal.hits, normal.misses, normal.loads, normal.evictions),
+        (1, 2, 2, 1)
+    );
+    assert_eq!((normal.stores, normal.store_failures), (1, 0));
+    let prometheus = cfg.prometheus_stats();
+    assert!(prometheus.contains(&format!(
+        "encrypt_config_cache_hits_total{{type=\"{}\"}} 1\n",
+        std::any::type_name::<NormalConfig>()
+    )));
+}
+
+#[test]
+fn entries_test() {
+    let cfg: Config<2> = Config::default();
+    let _normal = cfg.get::<NormalConfig>();
+    let mut another = cfg.get_mut::<AnotherConfig>();
+    *another = AnotherConfig;
+    let entries = cfg.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].type_name, std::any::type_name::<AnotherConfig>());
+    assert_eq!(entries[0].kind, SourceKind::Normal);
+    assert!(entries[0].dirty);
+    assert_eq!(entries[0].borrow, BorrowState::Writing);
+    assert_eq!(entries[1].type_name, std::any::type_name::<NormalConfig>());
+    assert!(!entries[1].dirty);
+    assert_eq!(entries[1].borrow, BorrowState::Reading(1));
+    assert_eq!(
+        (entries[1].path.as_ref(), entries[1].keyring_entry),
+        (None, None)
+    );
+}
+
+#[test]
+fn insert_test() {
+    let cfg: Config<2> = Config::builder().with(NormalConfig { value: 42 }).build();
+    assert_eq!(cfg.get::<NormalConfig>().value, 42);
+    assert!(!cfg.entries()[0].dirty);
+    {
+        let _normal = cfg.get::<NormalConfig>();
+        let res = cfg.insert(NormalConfig { value: 0 });
+        assert!(matches!(res, Err(ConfigError::AlreadyBorrowed { .. })));
+    }
+    cfg.insert(NormalConfig { value: 7 }).unwrap();
+    assert_eq!(cfg.replace(NormalConfig { value: 8 }).value, 7);
+    assert_eq!(cfg.get::<NormalConfig>().value, 8);
+    assert!(cfg.entries()[0].dirty);
+}
+
+#[derive(Default, NormalSource)]
+struct GlobalConfig {
+    value: i32,
+}
+
+#[test]
+fn global_test() {
+    encrypt_config::global().get_mut::<GlobalConfig>().value = 42;
+    std::thread::spawn(|| assert_eq!(encrypt_config::global().get::<GlobalConfig>().value, 42))
+        .join()
+        .unwrap();
+    encrypt_config::global().flush_all().unwrap();
+}
+
+#[derive(Validate)]
+struct ServerConfig {
+    #[config(range(min = 1, max = 65535))]
+    port: u32,
+    #[config(non_empty, one_of("debug", "info"))]
+    log_level: String,
+    #[config(path_exists)]
+    root: std::path::PathBuf,
+    #[config(nested)]
+    database: DatabaseConfig,
+}
+
+#[derive(Validate)]
+struct DatabaseConfig {
+    #[config(range(max = 10))]
+    pool: u8,
+    #[cfg_attr(feature = "regex", config(regex = "^[a-z]+$"))]
+    host: String,
+}
+
+#[test]
+fn validate_test() {
+    let mut server = ServerConfig {
+        port: 8080,
+        log_level: "info".to_owned(),
+        root: std::env::temp_dir(),
+        database: DatabaseConfig {
+            pool: 4,
+            host: "localhost".to_owned(),
+        },
+    };
+    assert!(server.validate().is_ok());
+    assert_eq!(server.database.host, "localhost");
+    server.port = 0;
+    server.log_level = "".to_owned();
+    server.root = "/not/exist".into();
+    server.database.pool = 11;
+    let errors = server.validate().unwrap_err();
+    let fields = errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        ["port", "log_level", "log_level", "root", "database.pool"]
+    );
+    assert_eq!(errors[0], FieldError::new("port", "must be in 1..=65535"));
+    #[cfg(feature = "regex")]
+    {
+        server.database.host = "LOCALHOST".to_owned();
+        let errors = server.validate().unwrap_err();
+        assert_eq!(errors.last().unwrap().field, "database.host");
+    }
+}
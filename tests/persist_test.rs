@@ -1,4 +1,8 @@
-use encrypt_config::{Config, PersistSource};
+use encrypt_config::{
+    backup::backup_path, error::ConfigError, Config, ConflictPolicy, Fallback, FieldError,
+    LockMode, PersistSource, SourceKind, Validate,
+};
+use fs4::FileExt;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Default, PersistSource)]
@@ -11,6 +15,16 @@ struct PersistConfig {
     value: i32,
 }
 
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "flush_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/flush_config.json"))
+)]
+struct FlushConfig {
+    value: i32,
+}
+
 #[test]
 fn persist_test() {
     std::fs::remove_file(PersistConfig::path()).ok();
@@ -33,3 +47,587 @@ fn persist_test() {
     }
     std::fs::remove_file(PersistConfig::path()).ok();
 }
+
+#[test]
+fn entries_test() {
+    let cfg: Config<1> = Config::default();
+    drop(cfg.get::<PersistConfig>());
+    let entry = &cfg.entries()[0];
+    assert_eq!(entry.kind, SourceKind::Persist);
+    assert_eq!(entry.path, Some(PersistConfig::path()));
+    assert!(!entry.dirty);
+}
+
+#[test]
+fn flush_test() {
+    std::fs::remove_file(FlushConfig::path()).ok();
+    let cfg: Config<1> = Config::default();
+    cfg.get_mut::<FlushConfig>().value = 42;
+    assert!(!FlushConfig::path().exists());
+    cfg.flush_all().unwrap();
+    assert_eq!(FlushConfig::load().unwrap().value, 42);
+    std::fs::remove_file(FlushConfig::path()).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "reload_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/reload_config.json"))
+)]
+struct ReloadConfig {
+    value: i32,
+}
+
+#[test]
+fn reload_test() {
+    std::fs::remove_file(ReloadConfig::path()).ok();
+    let cfg: Config<1> = Config::default();
+    assert_eq!(cfg.get::<ReloadConfig>().value, 0);
+    // edited by another tool
+    ReloadConfig { value: 42 }.store().unwrap();
+    assert_eq!(cfg.get::<ReloadConfig>().value, 0);
+    {
+        let _persist = cfg.get::<ReloadConfig>();
+        assert!(cfg.reload::<ReloadConfig>().is_err());
+        assert!(cfg.invalidate::<ReloadConfig>().is_err());
+    }
+    cfg.reload::<ReloadConfig>().unwrap();
+    assert_eq!(cfg.get::<ReloadConfig>().value, 42);
+    // changes not written back are discarded
+    cfg.get_mut::<ReloadConfig>().value = 0;
+    cfg.invalidate::<ReloadConfig>().unwrap();
+    assert_eq!(cfg.get::<ReloadConfig>().value, 42);
+    std::fs::remove_file(ReloadConfig::path()).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "transaction_config.json")
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/transaction_config.json"))
+)]
+struct TransactionConfig {
+    value: i32,
+}
+
+#[derive(Default, Clone)]
+struct ReadOnlyConfig;
+
+impl encrypt_config::Cacheable for ReadOnlyConfig {
+    fn load() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    fn store(&self) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn transaction_test() {
+    let fail = |cfg: &Config<2>| {
+        let res = cfg.transaction(|tx| {
+            tx.get_mut::<TransactionConfig>()?.value = 42;
+            tx.get_mut::<ReadOnlyConfig>()?;
+            Ok(())
+        });
+        let Err(ConfigError::CommitFailed {
+            rollback_errors, ..
+        }) = res
+        else {
+            panic!("commit should fail");
+        };
+        assert!(rollback_errors.is_empty());
+    };
+    let original = b"{ \"value\": 7 }\n";
+    std::fs::write(TransactionConfig::path(), original).unwrap();
+    let cfg: Config<2> = Config::default();
+    fail(&cfg);
+    // rolled back to the original file
+    assert_eq!(std::fs::read(TransactionConfig::path()).unwrap(), original);
+    assert_eq!(cfg.get::<TransactionConfig>().value, 7);
+    std::fs::remove_file(TransactionConfig::path()).ok();
+    cfg.invalidate::<TransactionConfig>().unwrap();
+    fail(&cfg);
+    // the file did not exist
+    assert!(!TransactionConfig::path().exists());
+    assert_eq!(cfg.get::<TransactionConfig>().value, 0);
+    cfg.transaction(|tx| {
+        tx.get_mut::<TransactionConfig>()?.value = 42;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(TransactionConfig::load().unwrap().value, 42);
+    assert_eq!(cfg.get::<TransactionConfig>().value, 42);
+    std::fs::remove_file(TransactionConfig::path()).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "validated_config.json", validate = Self::validate)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/validated_config.json"), validate = Self::validate)
+)]
+struct ValidatedConfig {
+    port: u16,
+}
+
+impl Validate for ValidatedConfig {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        match self.port {
+            0 => Err(vec![FieldError::new("port", "must not be 0")]),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource, Validate)]
+#[cfg_attr(feature = "default_config_dir", source(name = "ruled_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/ruled_config.json"))
+)]
+struct RuledConfig {
+    #[config(range(min = 1))]
+    port: u16,
+}
+
+#[test]
+fn validate_test() {
+    std::fs::remove_file(ValidatedConfig::path()).ok();
+    let cfg: Config<1> = Config::default();
+    cfg.get_mut::<ValidatedConfig>().port = 8080;
+    cfg.flush::<ValidatedConfig>().unwrap();
+    // hand-edited
+    std::fs::write(ValidatedConfig::path(), r#"{"port": 0}"#).unwrap();
+    let Err(ConfigError::ValidationFailed { errors, .. }) = cfg.reload::<ValidatedConfig>() else {
+        panic!("reload should fail");
+    };
+    assert_eq!(errors, vec![FieldError::new("port", "must not be 0")]);
+    assert_eq!(cfg.get::<ValidatedConfig>().port, 8080);
+    // never written back
+    cfg.get_mut::<ValidatedConfig>().port = 0;
+    let res = cfg.flush::<ValidatedConfig>();
+    assert!(matches!(res, Err(ConfigError::ValidationFailed { .. })));
+    cfg.invalidate::<ValidatedConfig>().unwrap();
+    std::fs::remove_file(ValidatedConfig::path()).ok();
+
+    // the field rules are checked if `validate` is not given
+    std::fs::remove_file(RuledConfig::path()).ok();
+    cfg.get_mut::<RuledConfig>().port = 0;
+    let Err(ConfigError::ValidationFailed { errors, .. }) = cfg.flush::<RuledConfig>() else {
+        panic!("flush should fail");
+    };
+    assert_eq!(errors, vec![FieldError::new("port", "must be at least 1")]);
+    cfg.invalidate::<RuledConfig>().unwrap();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "versioned_config.json", version = 2, migrations = [v0_to_v1, v1_to_v2])
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/versioned_config.json"), version = 2, migrations = [v0_to_v1, v1_to_v2])
+)]
+struct VersionedConfig {
+    host: String,
+    port: u16,
+}
+
+fn v0_to_v1(mut value: serde_json::Value) -> serde_json::Value {
+    if let Some(addr) = value.as_object_mut().and_then(|m| m.remove("addr")) {
+        value["host"] = addr;
+    }
+    value
+}
+
+fn v1_to_v2(mut value: serde_json::Value) -> serde_json::Value {
+    value["port"] = 8080.into();
+    value
+}
+
+#[test]
+fn migrate_test() {
+    let path = VersionedConfig::path();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    // unversioned file taken as version 0
+    std::fs::write(&path, r#"{"addr": "localhost"}"#).unwrap();
+    let cfg: Config<1> = Config::default();
+    {
+        let versioned = cfg.get::<VersionedConfig>();
+        assert_eq!(versioned.host, "localhost");
+        assert_eq!(versioned.port, 8080);
+    }
+    // rewritten in the new format
+    let written: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(
+        written,
+        serde_json::json!({"$version": 2, "host": "localhost", "port": 8080})
+    );
+
+    // newer files are refused
+    std::fs::write(&path, r#"{"$version": 3, "host": "localhost", "port": 80}"#).unwrap();
+    assert!(cfg.reload::<VersionedConfig>().is_err());
+    cfg.invalidate::<VersionedConfig>().unwrap();
+    std::fs::remove_file(&path).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "corrupt_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/corrupt_config.json"))
+)]
+struct CorruptConfig {
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "fallback_config.json", fallback = Fallback::Always)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/fallback_config.json"), fallback = Fallback::Always)
+)]
+struct FallbackConfig {
+    port: u16,
+}
+
+#[test]
+fn fallback_test() {
+    let path = CorruptConfig::path();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "{corrupt").unwrap();
+    let cfg: Config<2> = Config::default();
+    assert!(matches!(
+        cfg.try_get::<CorruptConfig>(),
+        Err(ConfigError::LoadFailed { .. })
+    ));
+    assert!(cfg.entries().is_empty());
+    // the broken file is left untouched
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{corrupt");
+    std::fs::remove_file(&path).ok();
+    // not found falls back to default
+    assert_eq!(cfg.try_get::<CorruptConfig>().unwrap().port, 0);
+
+    std::fs::write(FallbackConfig::path(), "{corrupt").unwrap();
+    assert_eq!(cfg.try_get::<FallbackConfig>().unwrap().port, 0);
+    cfg.invalidate::<FallbackConfig>().unwrap();
+    std::fs::remove_file(FallbackConfig::path()).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "backup_config.json", backups = 2)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/backup_config.json"), backups = 2)
+)]
+struct BackupConfig {
+    port: u16,
+}
+
+#[test]
+fn backup_test() {
+    let path = BackupConfig::path();
+    let dir = path.parent().unwrap();
+    let cleanup = || {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap().path();
+            if entry.to_string_lossy().contains("backup_config.json") {
+                std::fs::remove_file(entry).ok();
+            }
+        }
+    };
+    std::fs::create_dir_all(dir).unwrap();
+    cleanup();
+    let cfg: Config<1> = Config::default();
+    for port in 1..=4 {
+        cfg.get_mut::<BackupConfig>().port = port;
+        cfg.flush::<BackupConfig>().unwrap();
+    }
+    let read = |n| std::fs::read_to_string(backup_path(&path, n)).unwrap();
+    assert_eq!(read(1), r#"{"port":3}"#);
+    assert_eq!(read(2), r#"{"port":2}"#);
+    assert!(!backup_path(&path, 3).exists());
+
+    // recovered from the newest valid backup
+    std::fs::write(backup_path(&path, 1), "{corrupt").unwrap();
+    std::fs::write(&path, "{corrupt").unwrap();
+    cfg.reload::<BackupConfig>().unwrap();
+    assert_eq!(cfg.get::<BackupConfig>().port, 2);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"port":2}"#);
+    let quarantined = || {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("backup_config.json.corrupt-"))
+            .count()
+    };
+    assert_eq!(quarantined(), 1);
+
+    // refreshing on watching never touches the file, which may be half-written
+    std::fs::write(&path, "{corrupt").unwrap();
+    assert!(<BackupConfig as encrypt_config::Cacheable>::refresh().is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{corrupt");
+    assert_eq!(quarantined(), 1);
+    // quarantined again within the same second
+    cfg.reload::<BackupConfig>().unwrap();
+    assert_eq!(quarantined(), 2);
+    cfg.invalidate::<BackupConfig>().unwrap();
+    cleanup();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "atomic_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/atomic_config.json"))
+)]
+struct AtomicConfig {
+    port: u16,
+}
+
+#[test]
+fn atomic_test() {
+    let path = AtomicConfig::path();
+    let dir = path.parent().unwrap();
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(&path, r#"{"port":1}"#).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    }
+    let cfg: Config<1> = Config::default();
+    cfg.get_mut::<AtomicConfig>().port = 2;
+    cfg.flush::<AtomicConfig>().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"port":2}"#);
+    // the temporary file is renamed over, and the permissions are kept
+    let leftovers = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("atomic_config.json.tmp-"))
+        .count();
+    assert_eq!(leftovers, 0);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    cfg.invalidate::<AtomicConfig>().unwrap();
+    std::fs::remove_file(&path).ok();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "shared_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/shared_config.json"))
+)]
+struct SharedConfig {
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "owned_config.json", lock = LockMode::Exclusive)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/owned_config.json"), lock = LockMode::Exclusive)
+)]
+struct OwnedConfig {
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "unlocked_config.json", lock = LockMode::None)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/unlocked_config.json"), lock = LockMode::None)
+)]
+struct UnlockedConfig {
+    port: u16,
+}
+
+#[test]
+fn lock_test() {
+    let owner_of = |path: std::path::PathBuf| {
+        let mut path = path.into_os_string();
+        path.push(".owner");
+        std::fs::File::create(path).unwrap()
+    };
+    std::fs::create_dir_all(SharedConfig::path().parent().unwrap()).unwrap();
+    std::fs::remove_file(SharedConfig::path()).ok();
+    // as if another process owned the file
+    let owner = owner_of(SharedConfig::path());
+    FileExt::lock(&owner).unwrap();
+    let cfg: Config<2> = Config::default();
+    cfg.get_mut::<SharedConfig>().port = 1;
+    assert!(matches!(
+        cfg.flush::<SharedConfig>(),
+        Err(ConfigError::Locked { .. })
+    ));
+    drop(owner);
+    cfg.flush::<SharedConfig>().unwrap();
+    cfg.invalidate::<SharedConfig>().unwrap();
+    std::fs::remove_file(SharedConfig::path()).ok();
+
+    // no sidecar file is created if not locked
+    cfg.get::<UnlockedConfig>();
+    let mut sidecar = UnlockedConfig::path().into_os_string();
+    sidecar.push(".lock");
+    assert!(!std::path::Path::new(&sidecar).exists());
+    cfg.invalidate::<UnlockedConfig>().unwrap();
+
+    // owned by this process once loaded
+    cfg.get::<OwnedConfig>();
+    assert!(FileExt::try_lock(&owner_of(OwnedConfig::path())).is_err());
+    cfg.invalidate::<OwnedConfig>().unwrap();
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "conflict_config.json", conflict = ConflictPolicy::Fail)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/conflict_config.json"), conflict = ConflictPolicy::Fail)
+)]
+struct ConflictConfig {
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "keep_theirs_config.json", conflict = ConflictPolicy::KeepTheirs)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/keep_theirs_config.json"), conflict = ConflictPolicy::KeepTheirs)
+)]
+struct KeepTheirsConfig {
+    port: u16,
+}
+
+/// Merged by default.
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(feature = "default_config_dir", source(name = "merge_config.json"))]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/merge_config.json"))
+)]
+struct MergeConfig {
+    host: String,
+    port: u16,
+}
+
+#[test]
+fn conflict_test() {
+    let path = ConflictConfig::path();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, r#"{"port":1}"#).unwrap();
+    let cfg: Config<2> = Config::default();
+    cfg.get_mut::<ConflictConfig>().port = 2;
+    // edited by hand
+    std::fs::write(&path, r#"{"port":9}"#).unwrap();
+    assert!(matches!(
+        cfg.flush::<ConflictConfig>(),
+        Err(ConfigError::Conflict { .. })
+    ));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"port":9}"#);
+    cfg.reload::<ConflictConfig>().unwrap();
+    cfg.get_mut::<ConflictConfig>().port = 2;
+    cfg.flush::<ConflictConfig>().unwrap();
+    cfg.invalidate::<ConflictConfig>().unwrap();
+    std::fs::remove_file(&path).ok();
+
+    // the file edited is kept, until reloaded
+    let path = KeepTheirsConfig::path();
+    std::fs::write(&path, r#"{"port":1}"#).unwrap();
+    cfg.get_mut::<KeepTheirsConfig>().port = 2;
+    std::fs::write(&path, r#"{"port":9}"#).unwrap();
+    cfg.flush::<KeepTheirsConfig>().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"port":9}"#);
+    cfg.get_mut::<KeepTheirsConfig>().port = 3;
+    cfg.flush::<KeepTheirsConfig>().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"port":9}"#);
+    cfg.reload::<KeepTheirsConfig>().unwrap();
+    assert_eq!(cfg.get::<KeepTheirsConfig>().port, 9);
+    cfg.get_mut::<KeepTheirsConfig>().port = 3;
+    cfg.flush::<KeepTheirsConfig>().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"port":3}"#);
+    cfg.invalidate::<KeepTheirsConfig>().unwrap();
+    std::fs::remove_file(&path).ok();
+
+    // the fields changed by only one side are merged
+    let path = MergeConfig::path();
+    std::fs::write(&path, r#"{"host":"a","port":1}"#).unwrap();
+    cfg.get_mut::<MergeConfig>().port = 2;
+    std::fs::write(&path, r#"{"host":"b","port":1}"#).unwrap();
+    cfg.flush::<MergeConfig>().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"{"host":"b","port":2}"#
+    );
+    // the fields merged from the file are kept on the next write-back
+    cfg.get_mut::<MergeConfig>().port = 3;
+    cfg.flush::<MergeConfig>().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"{"host":"b","port":3}"#
+    );
+    // but conflict if changed in the cache, which never saw them, until reloaded
+    cfg.get_mut::<MergeConfig>().host = "c".into();
+    let Err(ConfigError::Conflict { fields, .. }) = cfg.flush::<MergeConfig>() else {
+        panic!("flush should fail");
+    };
+    assert_eq!(fields, vec!["host".to_owned()]);
+    cfg.reload::<MergeConfig>().unwrap();
+    assert_eq!(cfg.get::<MergeConfig>().host, "b");
+    cfg.get_mut::<MergeConfig>().host = "c".into();
+    cfg.flush::<MergeConfig>().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"{"host":"c","port":3}"#
+    );
+    // the fields changed differently by both sides conflict
+    cfg.get_mut::<MergeConfig>().port = 5;
+    std::fs::write(&path, r#"{"host":"c","port":4}"#).unwrap();
+    let Err(ConfigError::Conflict { fields, .. }) = cfg.flush::<MergeConfig>() else {
+        panic!("flush should fail");
+    };
+    assert_eq!(fields, vec!["port".to_owned()]);
+    cfg.invalidate::<MergeConfig>().unwrap();
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,52 @@
+use encrypt_config::{Config, ConflictPolicy, PersistSource};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Overwriting, since the file is also written by the test as another tool, even over a bad edit.
+#[derive(Serialize, Deserialize, Default, PersistSource)]
+#[cfg_attr(
+    feature = "default_config_dir",
+    source(name = "watch_config.json", conflict = ConflictPolicy::Overwrite)
+)]
+#[cfg_attr(
+    not(feature = "default_config_dir"),
+    source(path = const_str::concat!(encrypt_config::TEST_OUT_DIR, "/watch_config.json"), conflict = ConflictPolicy::Overwrite)
+)]
+struct WatchConfig {
+    value: i32,
+}
+
+/// Wait until the watcher refreshed the value.
+fn wait_for(cfg: &Config<1>, value: i32) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if cfg.get::<WatchConfig>().value == value {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn watch_test() {
+    std::fs::remove_file(WatchConfig::path()).ok();
+    let cfg: Config<1> = Config::default();
+    let _watcher = cfg.watch::<WatchConfig>().unwrap();
+    assert_eq!(cfg.get::<WatchConfig>().value, 0);
+    // edited by another tool
+    WatchConfig { value: 42 }.store().unwrap();
+    assert!(wait_for(&cfg, 42));
+    // a bad edit never replaces the good value
+    std::fs::write(WatchConfig::path(), "{ bad json").unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(cfg.get::<WatchConfig>().value, 42);
+    // refreshed after the ref released
+    {
+        let _persist = cfg.get::<WatchConfig>();
+        WatchConfig { value: 7 }.store().unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    assert!(wait_for(&cfg, 7));
+    std::fs::remove_file(WatchConfig::path()).ok();
+}